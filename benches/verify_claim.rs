@@ -0,0 +1,109 @@
+// Cost of verifying a claim for a block in the middle of chains of growing length.
+// MMR proof and storage proof are timed on their own as well, MMR proof grows with
+// the chain while storage proof only depends on the para state.
+
+use beefy_lc_demo::{
+    generate_beefy_pairs_from_seed, generate_signer_proofs, BlockStream, ClaimProof, EthereumActor,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const CHAIN_LENGTHS: [usize; 4] = [10, 100, 1000, 10000];
+
+// Actor which finalized the last block of a chain of `length` blocks and a claim of
+// the block in the middle of it. Only the blocks needed are kept around.
+fn finalized_claim(length: usize) -> (EthereumActor, ClaimProof) {
+    let authorities = generate_beefy_pairs_from_seed(5, 0);
+    let authority_ids = authorities
+        .iter()
+        .map(|(_, id)| id.clone())
+        .collect::<Vec<_>>();
+    let middle = length / 2;
+
+    let (mut genesis, mut claimed, mut child, mut last) = (None, None, None, None);
+    // Only the last block carries a commitment
+    for (position, block) in BlockStream::new(authorities, length as u64, 0)
+        .take(length)
+        .enumerate()
+    {
+        if position == 0 {
+            genesis = Some(block);
+        } else if position == middle {
+            claimed = Some(block);
+        } else if position == middle + 1 {
+            child = Some(block);
+        } else if position == length - 1 {
+            last = Some(block);
+        }
+    }
+    let (genesis, claimed, child, last) = (
+        genesis.unwrap(),
+        claimed.unwrap(),
+        child.unwrap(),
+        last.unwrap(),
+    );
+
+    let mut actor = EthereumActor::from_genesis(&genesis, 10, None);
+    actor
+        .ingest_new_header(
+            last.ethereum_view(),
+            generate_signer_proofs(&authority_ids, &[0, 1, 2, 3, 4]),
+        )
+        .unwrap();
+    let claim_proof = claimed.build_claim_proof(&child, &last);
+    actor.verify_claim_proof(None, &claim_proof).unwrap();
+    (actor, claim_proof)
+}
+
+fn verify_claim(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_claim");
+    for &length in CHAIN_LENGTHS.iter() {
+        let (actor, claim_proof) = finalized_claim(length);
+        println!(
+            "{} blocks: {} MMR proof items",
+            length,
+            claim_proof.mmr_proof_items.len()
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("full", length),
+            &claim_proof,
+            |b, claim_proof| b.iter(|| actor.verify_claim_proof(None, claim_proof).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("mmr", length),
+            &claim_proof,
+            |b, claim_proof| {
+                b.iter(|| {
+                    actor
+                        .verify_finalized_para_root(
+                            None,
+                            claim_proof.relay_header.clone(),
+                            claim_proof.mmr_proof_items.clone(),
+                            claim_proof.block_pos_in_mmr,
+                            claim_proof.para_header_merkle_root,
+                        )
+                        .unwrap()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("trie", length),
+            &claim_proof,
+            |b, claim_proof| {
+                b.iter(|| {
+                    actor
+                        .verify_storage_against_root(
+                            claim_proof.para_header.state_root,
+                            claim_proof.claimed_kv.clone(),
+                            claim_proof.kv_proof.clone(),
+                        )
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, verify_claim);
+criterion_main!(benches);
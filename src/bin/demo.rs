@@ -1,5 +1,7 @@
 use beefy_lc_demo::beefy_light_client_demo;
+use std::io;
+use std::path::Path;
 
-fn main() {
-    beefy_light_client_demo();
+fn main() -> io::Result<()> {
+    beefy_light_client_demo(Path::new("fixtures/last_block.json"))
 }
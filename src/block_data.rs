@@ -1,57 +1,189 @@
-use crate::block_generation::CommitmentPayload;
+use crate::claim_proof::ClaimProof;
+use crate::commitment::CommitmentPayload;
+use crate::error::VerificationError;
+use crate::ethereum_actor::{verify_para_head_proof, verify_storage_proof};
 use crate::ethereum_view::EthereumView;
-use crate::mmr::{MMRNode, MergeStrategy};
-use crate::types::{BlockNumber, HashOutput, HashingAlgo, LeafData, TestHeader};
-use crate::utils::mmr_size_from_number_of_leaves;
+use crate::mmr::{mmr_from_store, MMRNode, MmrStore};
+use crate::traits::{BeefyLeaf, NoDomain, ParaHeadKey};
+use crate::types::{HashOutput, HashingAlgo, LeafData, ParaId, TestHeader};
+use crate::utils::{build_para_heads_trie, empty_mmr_root, prove_para_head_inclusion, HashKeyed};
 use beefy_primitives::crypto::{AuthorityId, Pair};
 use beefy_primitives::SignedCommitment;
-use codec::{Decode, Encode};
-use mmr_lib::util::{MemMMR, MemStore};
+use codec::Encode;
+use mmr_lib::util::MemStore;
+use sp_core::hexdisplay::HexDisplay;
+use sp_core::sp_std::marker::PhantomData;
+use sp_core::Hasher;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::vec::Vec;
 
-pub struct BlockData {
+// Para head as stored in para heads merkle tree
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParaHeadEntry {
+    pub key: Vec<u8>,
+    pub encoded_header: Vec<u8>,
+}
+
+impl ParaHeadEntry {
+    // Key is derived from the header using `K`
+    pub fn new<K: ParaHeadKey>(para_id: ParaId, para_header: &TestHeader) -> Self {
+        Self {
+            key: K::key(para_id, para_header),
+            encoded_header: para_header.encode(),
+        }
+    }
+}
+
+// Hasher `H` is used for beefy MMR, para heads trie and para storage trie,
+// `K` is how para heads are keyed in para heads trie, `L` is the beefy MMR leaf
+// and `S` is where beefy MMR nodes are kept
+#[derive(Clone)]
+pub struct BlockData<
+    H = HashingAlgo,
+    K = HashKeyed<H>,
+    L: BeefyLeaf = LeafData<H>,
+    S = MemStore<MMRNode<L>>,
+> {
     // Beefy mmr store
-    pub beefy_mmr_store: MemStore<MMRNode<LeafData>>,
+    pub beefy_mmr_store: S,
     pub beefy_mmr_leaves: u64,
     // Header must contain digest entry for MMR root
     pub relay_header: TestHeader,
     // Optional signed commitment for this block
-    pub signed_commitment: Option<SignedCommitment<BlockNumber, CommitmentPayload<LeafData>>>,
+    pub signed_commitment: Option<SignedCommitment<u64, CommitmentPayload<L>>>,
 
     // Current Beefy authority set
     pub current_authority_set: Vec<(Pair, AuthorityId)>,
     pub current_authority_set_id: u64,
 
+    // Parachain whose header and storage claim is tracked by this block
+    pub para_id: ParaId,
     // Parachain header
     pub para_header: TestHeader,
-    pub encoded_para_head_data: Vec<(HashOutput, Vec<u8>)>,
+    // Headers of the rest of the parachains
+    pub other_para_headers: BTreeMap<ParaId, TestHeader>,
+    pub encoded_para_head_data: Vec<ParaHeadEntry>,
     // Proof of existence of selected kv pair in parachain header's storage root
     pub para_header_merkle_proof: Vec<Vec<u8>>,
     pub para_header_merkle_root: HashOutput,
     pub chosen_kv_proof: Vec<Vec<u8>>,
     pub chosen_kv_pair: (Vec<u8>, Vec<u8>),
+
+    pub(crate) hasher: PhantomData<H>,
+    pub(crate) para_head_key: PhantomData<K>,
 }
 
-impl BlockData {
-    pub fn ethereum_view(&self) -> EthereumView {
-        let mem_mmr = MemMMR::<_, MergeStrategy<LeafData, HashingAlgo>>::new(
-            mmr_size_from_number_of_leaves(self.beefy_mmr_leaves),
-            self.beefy_mmr_store.clone(),
+impl<H: Hasher<Out = HashOutput>, K: ParaHeadKey, L: BeefyLeaf, S: MmrStore<MMRNode<L>>>
+    BlockData<H, K, L, S>
+{
+    // Concise one line description of the block, useful to follow how chain evolves
+    pub fn summary(&self) -> String {
+        format!(
+            "relay block: {}, para block: {}, authority set id: {}, mmr leaves: {}, \
+            signed commitment: {}, chosen key: 0x{}",
+            self.relay_header.number,
+            self.para_header.number,
+            self.current_authority_set_id,
+            self.beefy_mmr_leaves,
+            self.signed_commitment.is_some(),
+            HexDisplay::from(&self.chosen_kv_pair.0),
+        )
+    }
+
+    // Checks that proofs carried by the block verify against its own roots
+    pub fn validate(&self) -> Result<(), VerificationError> {
+        verify_para_head_proof::<H, K>(
+            self.para_id,
+            &self.para_header,
+            self.para_header_merkle_root,
+            self.para_header_merkle_proof.clone(),
+        )?;
+        verify_storage_proof::<H>(
+            self.para_header.state_root,
+            self.chosen_kv_pair.clone(),
+            self.chosen_kv_proof.clone(),
+        )
+    }
+
+    // Proof of the chosen kv pair of this block. `child_block` provides para heads
+    // merkle root of this block, MMR proof is generated from `finalized_block`'s MMR.
+    pub fn build_claim_proof(
+        &self,
+        child_block: &BlockData<H, K, L, S>,
+        finalized_block: &BlockData<H, K, L, S>,
+    ) -> ClaimProof<L> {
+        // Leaf of this block is appended right after leaves of its own MMR
+        let block_pos_in_mmr = mmr_lib::leaf_index_to_pos(self.beefy_mmr_leaves);
+        let mmr = mmr_from_store::<L, H, NoDomain, S>(
+            finalized_block.beefy_mmr_leaves,
+            &finalized_block.beefy_mmr_store,
         );
-        let root = mem_mmr.get_root().unwrap();
+        let mmr_proof_items = mmr
+            .gen_proof(vec![block_pos_in_mmr])
+            .unwrap()
+            .proof_items()
+            .to_vec();
 
-        let cloned_signed_commitment = if self.signed_commitment.is_none() {
-            None
+        let (para_header_merkle_root, memdb) =
+            build_para_heads_trie::<H>(&child_block.encoded_para_head_data);
+        let para_header_inclusion_proof = prove_para_head_inclusion(
+            &memdb,
+            para_header_merkle_root,
+            K::key(self.para_id, &self.para_header),
+        );
+
+        ClaimProof {
+            relay_header: self.relay_header.clone(),
+            mmr_proof_items,
+            block_pos_in_mmr: block_pos_in_mmr.into(),
+            para_id: self.para_id,
+            para_header: self.para_header.clone(),
+            para_header_merkle_root,
+            para_header_inclusion_proof,
+            claimed_kv: self.chosen_kv_pair.clone(),
+            kv_proof: self.chosen_kv_proof.clone(),
+        }
+    }
+
+    pub fn ethereum_view(&self) -> EthereumView<L> {
+        self.view_of_mmr(self.beefy_mmr_leaves)
+    }
+
+    // View with MMR root as it was when the MMR had `leaf_count` leaves, used to prove
+    // claims against older roots. Signed commitment only covers the full MMR root,
+    // so it is left out of a view of a smaller MMR.
+    pub fn ethereum_view_at(&self, leaf_count: u64) -> Result<EthereumView<L>, VerificationError> {
+        if leaf_count > self.beefy_mmr_leaves {
+            return Err(VerificationError::MmrLeavesOutOfRange {
+                claimed: leaf_count,
+                finalized: self.beefy_mmr_leaves,
+            });
+        }
+        Ok(self.view_of_mmr(leaf_count))
+    }
+
+    // `leaf_count` must not be larger than leaves of the block's MMR
+    fn view_of_mmr(&self, leaf_count: u64) -> EthereumView<L> {
+        let root = if leaf_count == 0 {
+            empty_mmr_root()
+        } else {
+            mmr_from_store::<L, H, NoDomain, S>(leaf_count, &self.beefy_mmr_store)
+                .get_root()
+                .unwrap()
+        };
+        let signed_commitment = if leaf_count == self.beefy_mmr_leaves {
+            self.signed_commitment.clone()
         } else {
-            let encoded_signed_commitment = self.signed_commitment.as_ref().encode();
-            Decode::decode(&mut encoded_signed_commitment.as_slice()).unwrap()
+            None
         };
 
         EthereumView {
             beefy_mmr_root: root,
-            beefy_mmr_leaves: self.beefy_mmr_leaves,
+            beefy_mmr_leaves: leaf_count,
             relay_header: self.relay_header.clone(),
-            signed_commitment: cloned_signed_commitment,
+            para_id: self.para_id,
+            signed_commitment,
             para_header: self.para_header.clone(),
             para_header_merkle_proof: self.para_header_merkle_proof.clone(),
             chosen_kv_proof: self.chosen_kv_proof.clone(),
@@ -60,3 +192,90 @@ impl BlockData {
         }
     }
 }
+
+// Authority pairs contain secret keys, so only their public part is printed
+impl<H, K, L: BeefyLeaf, S> fmt::Debug for BlockData<H, K, L, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockData")
+            .field("beefy_mmr_leaves", &self.beefy_mmr_leaves)
+            .field("relay_header", &self.relay_header)
+            .field("has_signed_commitment", &self.signed_commitment.is_some())
+            .field(
+                "current_authority_set",
+                &self
+                    .current_authority_set
+                    .iter()
+                    .map(|(_, id)| id)
+                    .collect::<Vec<&AuthorityId>>(),
+            )
+            .field("current_authority_set_id", &self.current_authority_set_id)
+            .field("para_id", &self.para_id)
+            .field("para_header", &self.para_header)
+            .field("other_para_headers", &self.other_para_headers)
+            .field("para_header_merkle_root", &self.para_header_merkle_root)
+            .field("chosen_kv_pair", &self.chosen_kv_pair)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_generation::ChainBuilder;
+    use crate::demo::generate_beefy_pairs_from_seed;
+
+    fn chain() -> Vec<BlockData> {
+        ChainBuilder::with_seed(generate_beefy_pairs_from_seed(5, 0), 0)
+            .push_block()
+            .push_block()
+            .push_block()
+            .push_block_with_commitment()
+            .build()
+    }
+
+    #[test]
+    fn view_at_smaller_mmr_has_root_of_that_mmr() {
+        let blocks = chain();
+        assert_eq!(blocks[4].beefy_mmr_leaves, 4);
+        assert!(blocks[4].ethereum_view_at(4).unwrap() == blocks[4].ethereum_view());
+
+        let view = blocks[4].ethereum_view_at(2).unwrap();
+        assert_eq!(view.beefy_mmr_leaves, 2);
+        assert_eq!(
+            view.beefy_mmr_root,
+            blocks[2].ethereum_view().beefy_mmr_root
+        );
+        assert_ne!(
+            view.beefy_mmr_root,
+            blocks[4].ethereum_view().beefy_mmr_root
+        );
+
+        let view = blocks[4].ethereum_view_at(0).unwrap();
+        assert_eq!(view.beefy_mmr_leaves, 0);
+        assert_eq!(view.beefy_mmr_root, empty_mmr_root::<LeafData>());
+    }
+
+    #[test]
+    fn view_carries_signed_commitment_of_full_mmr_only() {
+        let blocks = chain();
+        assert!(blocks[4].signed_commitment.is_some());
+        assert!(blocks[4].ethereum_view().signed_commitment == blocks[4].signed_commitment);
+        assert!(blocks[4]
+            .ethereum_view_at(3)
+            .unwrap()
+            .signed_commitment
+            .is_none());
+    }
+
+    #[test]
+    fn view_at_larger_mmr_is_rejected() {
+        let blocks = chain();
+        assert!(matches!(
+            blocks[4].ethereum_view_at(5),
+            Err(VerificationError::MmrLeavesOutOfRange {
+                claimed: 5,
+                finalized: 4
+            })
+        ));
+    }
+}
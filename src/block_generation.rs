@@ -1,32 +1,53 @@
-use crate::block_data::BlockData;
-use crate::mmr::{MMRNode, MergeStrategy};
-use crate::traits::Hashable;
-use crate::types::{HashingAlgo, LeafData, TestHeader, TrieLayout};
-use crate::utils::mmr_size_from_number_of_leaves;
+use crate::block_data::{BlockData, ParaHeadEntry};
+use crate::commitment::{CommitmentPayload, SigningScheme};
+use crate::demo::generate_beefy_pairs_from_seed;
+use crate::mmr::{MMRNode, MmrAppender, MmrStore};
+use crate::traits::{BeefyLeaf, ParaHeadKey};
+use crate::types::{HashOutput, HashingAlgo, LeafData, ParaId, TestHeader, ValidatorSetId};
+use crate::utils::{
+    authority_set_digest_item, build_para_heads_trie, mmr_root_digest_item,
+    prove_para_head_inclusion, HashKeyed,
+};
 use beefy_primitives::crypto::{AuthorityId, AuthoritySignature, Pair};
 use beefy_primitives::{Commitment, SignedCommitment};
-use codec::{Decode, Encode};
-use mmr_lib::util::{MemMMR, MemStore};
+use codec::Encode;
+use mmr_lib::util::MemStore;
 use rand::prelude::*;
 use rand::rngs::StdRng;
 use sp_core::crypto::Pair as _;
-use sp_core::{Hasher, KeccakHasher};
-use sp_runtime::RuntimeAppPublic;
-use sp_trie::{MemoryDB, TrieDBMut, TrieMut};
+use sp_core::ecdsa;
+use sp_core::hashing::keccak_256;
+use sp_core::sp_std::marker::PhantomData;
+use sp_core::Hasher;
+use sp_trie::{Layout, Trie, TrieMut};
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
 use std::vec::Vec;
 
-#[derive(Encode, Decode)]
-pub struct CommitmentPayload<Leaf: Hashable + Encode + Decode> {
-    pub mmr_node: MMRNode<Leaf>,
-    pub changed_authority_ids: Option<Vec<AuthorityId>>,
-    pub new_validator_set_id: u64,
+// Parachain tracked by blocks created with `create_random_child_block`
+pub const DEFAULT_PARA_ID: ParaId = 2000;
+
+fn sign_commitment(
+    pair: &Pair,
+    encoded_commitment: &[u8],
+    signing_scheme: SigningScheme,
+) -> AuthoritySignature {
+    match signing_scheme {
+        SigningScheme::Raw => pair.sign(encoded_commitment),
+        SigningScheme::Keccak256 => AsRef::<ecdsa::Pair>::as_ref(pair)
+            .sign_prehashed(&keccak_256(encoded_commitment))
+            .into(),
+    }
 }
 
-fn generate_signed_commitment<TBlockNumber: Encode, TPayload: Encode>(
+pub fn generate_signed_commitment<TBlockNumber: Encode, TPayload: Encode>(
     set_id: u64,
     block_number: TBlockNumber,
     payload: TPayload,
     validator_pairs: &Vec<Pair>,
+    // Positions of validators which should not sign (e.g. offline validators)
+    missing_signature_positions: &[usize],
+    signing_scheme: SigningScheme,
 ) -> SignedCommitment<TBlockNumber, TPayload> {
     let commitment = Commitment {
         payload,
@@ -36,7 +57,18 @@ fn generate_signed_commitment<TBlockNumber: Encode, TPayload: Encode>(
 
     let signatures: Vec<Option<AuthoritySignature>> = validator_pairs
         .iter()
-        .map(|k| Some(k.sign(commitment.encode().as_ref())))
+        .enumerate()
+        .map(|(i, k)| {
+            if missing_signature_positions.contains(&i) {
+                None
+            } else {
+                Some(sign_commitment(
+                    k,
+                    commitment.encode().as_ref(),
+                    signing_scheme,
+                ))
+            }
+        })
         .collect();
 
     SignedCommitment {
@@ -45,67 +77,125 @@ fn generate_signed_commitment<TBlockNumber: Encode, TPayload: Encode>(
     }
 }
 
-pub fn verify_signed_commitment<TBlockNumber: Encode, TPayload: Encode>(
-    signed_commitment: &SignedCommitment<TBlockNumber, TPayload>,
-    initial_authorities: Vec<AuthorityId>,
-) -> Result<(), String> {
-    if signed_commitment.signatures.len() != initial_authorities.len() {
-        return Err("Number of signatures differ".to_string());
-    }
+// Random storage with `num_entries` kv pairs (random if not given) and proof of the pair
+// inserted at `proof_for_index` (random if not given)
+pub fn generate_random_storage_and_proof<H: Hasher<Out = HashOutput>>(
+    rng: &mut StdRng,
+    num_entries: Option<usize>,
+    proof_for_index: Option<usize>,
+) -> (
+    sp_trie::MemoryDB<H>,
+    HashOutput,
+    (Vec<u8>, Vec<u8>),
+    Vec<Vec<u8>>,
+) {
+    let num_entries = num_entries.unwrap_or_else(|| (rng.next_u64() % 100 + 2) as usize);
+    let generate_proof_for_index =
+        proof_for_index.unwrap_or_else(|| (rng.next_u64() % num_entries as u64) as usize);
 
-    let encoded_commitment = signed_commitment.commitment.encode();
-    for (i, maybe_signature) in signed_commitment.signatures.iter().enumerate() {
-        if maybe_signature.is_none() {
-            return Err("No signature at a position".to_string());
-        }
-        if !initial_authorities[i].verify(&encoded_commitment, &maybe_signature.as_ref().unwrap()) {
-            return Err("Signature is invalid".to_string());
-        }
-    }
-    Ok(())
+    let (trie_db, trie_root, mut chosen_kv_pairs, proof) =
+        generate_random_storage_and_multiproof::<H>(rng, num_entries, &[generate_proof_for_index]);
+
+    (trie_db, trie_root, chosen_kv_pairs.remove(0), proof)
 }
 
-fn generate_random_storage_and_proof() -> (
-    sp_trie::MemoryDB<sp_core::KeccakHasher>,
-    <sp_core::KeccakHasher as Hasher>::Out,
-    (Vec<u8>, Vec<u8>),
+// Random storage with `num_entries` kv pairs and a single proof covering
+// all pairs inserted at `proof_for_indices`
+pub fn generate_random_storage_and_multiproof<H: Hasher<Out = HashOutput>>(
+    rng: &mut StdRng,
+    num_entries: usize,
+    proof_for_indices: &[usize],
+) -> (
+    sp_trie::MemoryDB<H>,
+    HashOutput,
+    Vec<(Vec<u8>, Vec<u8>)>,
     Vec<Vec<u8>>,
 ) {
-    let mut rng = StdRng::from_entropy();
-    let random_kvs = rng.next_u64() % 100 + 1;
-    let generate_proof_for_index = rng.next_u64() % random_kvs;
+    assert!(
+        proof_for_indices.iter().all(|index| *index < num_entries),
+        "Proof index must point to one of the entries"
+    );
+
+    let (trie_db, trie_root, kv_pairs) = generate_random_storage::<H>(rng, num_entries);
+    let chosen_kv_pairs = kv_pairs
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| proof_for_indices.contains(index))
+        .map(|(_, kv_pair)| kv_pair)
+        .collect::<Vec<_>>();
 
-    let mut trie_db = sp_trie::MemoryDB::<sp_core::KeccakHasher>::default();
-    let mut trie_root = sp_trie::empty_trie_root::<TrieLayout>();
+    let proof = sp_trie::generate_trie_proof::<Layout<H>, _, _, _>(
+        &trie_db,
+        trie_root,
+        chosen_kv_pairs.iter().map(|(key, _)| key),
+    )
+    .unwrap();
 
-    let mut chosen_key = [0u8; 32];
-    let mut chosen_value = [0u8; 64];
+    (trie_db, trie_root, chosen_kv_pairs, proof)
+}
+
+// Random storage with `num_entries` kv pairs and proof that pairs with keys from the
+// `lo`-th to the `hi`-th smallest one are all the pairs in that key range. Proof holds
+// raw trie nodes visited while iterating the range, as `verify_key_range_claim` expects.
+pub fn generate_random_storage_and_range_proof<H: Hasher<Out = HashOutput>>(
+    rng: &mut StdRng,
+    num_entries: usize,
+    lo: usize,
+    hi: usize,
+) -> (
+    sp_trie::MemoryDB<H>,
+    HashOutput,
+    Vec<(Vec<u8>, Vec<u8>)>,
+    Vec<Vec<u8>>,
+) {
+    assert!(
+        lo <= hi && hi < num_entries,
+        "Range must be within the entries"
+    );
+
+    let (trie_db, trie_root, mut kv_pairs) = generate_random_storage::<H>(rng, num_entries);
+    kv_pairs.sort();
+
+    // Iteration visits the paths to every key in range and to the first key past it
+    let trie = sp_trie::TrieDB::<Layout<H>>::new(&trie_db, &trie_root).unwrap();
+    let mut recorder = sp_trie::Recorder::new();
+    for (key, _) in kv_pairs[lo..(hi + 2).min(num_entries)].iter() {
+        trie.get_with(key, &mut recorder).unwrap();
+    }
+    let proof = recorder
+        .drain()
+        .into_iter()
+        .map(|record| record.data)
+        .collect::<BTreeSet<Vec<u8>>>()
+        .into_iter()
+        .collect();
+
+    (trie_db, trie_root, kv_pairs[lo..=hi].to_vec(), proof)
+}
+
+// Random storage with `num_entries` kv pairs, returned in insertion order
+fn generate_random_storage<H: Hasher<Out = HashOutput>>(
+    rng: &mut StdRng,
+    num_entries: usize,
+) -> (sp_trie::MemoryDB<H>, HashOutput, Vec<(Vec<u8>, Vec<u8>)>) {
+    let mut trie_db = sp_trie::MemoryDB::<H>::default();
+    let mut trie_root = sp_trie::empty_trie_root::<Layout<H>>();
+
+    let mut kv_pairs = vec![];
 
     {
-        let mut trie = sp_trie::TrieDBMut::<TrieLayout>::new(&mut trie_db, &mut trie_root);
+        let mut trie = sp_trie::TrieDBMut::<Layout<H>>::new(&mut trie_db, &mut trie_root);
         let mut key = [0u8; 32];
         let mut value = [0u8; 64];
-        for i in 0..random_kvs + 1 {
+        for _ in 0..num_entries {
             rng.fill(&mut key);
             rng.fill(&mut value);
             trie.insert(&key, &value).unwrap();
-            if i == generate_proof_for_index {
-                chosen_key.copy_from_slice(&key);
-                chosen_value.copy_from_slice(&value);
-            }
+            kv_pairs.push((key.to_vec(), value.to_vec()));
         }
     }
 
-    let proof =
-        sp_trie::generate_trie_proof::<TrieLayout, _, _, _>(&trie_db, trie_root, vec![&chosen_key])
-            .unwrap();
-
-    return (
-        trie_db,
-        trie_root,
-        (chosen_key.to_vec(), chosen_value.to_vec()),
-        proof,
-    );
+    (trie_db, trie_root, kv_pairs)
 }
 
 pub fn create_random_child_block(
@@ -113,187 +203,761 @@ pub fn create_random_child_block(
     should_generate_commitment: bool,
     new_authority_set: Option<Vec<(Pair, AuthorityId)>>,
 ) -> BlockData {
+    create_random_child_block_with_rng(
+        block_data,
+        should_generate_commitment,
+        new_authority_set,
+        &mut StdRng::from_entropy(),
+    )
+}
+
+// Same as `create_random_child_block` but storage is generated from given rng,
+// so chain built from the same seed is reproducible
+pub fn create_random_child_block_with_rng(
+    block_data: Option<&BlockData>,
+    should_generate_commitment: bool,
+    new_authority_set: Option<Vec<(Pair, AuthorityId)>>,
+    rng: &mut StdRng,
+) -> BlockData {
+    create_random_child_block_with_hasher::<HashingAlgo, HashKeyed, LeafData>(
+        block_data,
+        should_generate_commitment,
+        new_authority_set,
+        rng,
+    )
+}
+
+// Creates genesis block tracking parachain `para_id` along with
+// other parachains whose heads are part of the para heads merkle root
+pub fn create_genesis_block_with_paras<
+    H: Hasher<Out = HashOutput>,
+    K: ParaHeadKey,
+    L: BeefyLeaf,
+>(
+    new_authority_set: Vec<(Pair, AuthorityId)>,
+    para_id: ParaId,
+    other_para_ids: &[ParaId],
+    rng: &mut StdRng,
+) -> BlockData<H, K, L> {
+    create_genesis_block_with_store(
+        MemStore::default(),
+        new_authority_set,
+        para_id,
+        other_para_ids,
+        rng,
+    )
+}
+
+// Same as `create_genesis_block_with_paras` but beefy MMR is kept in `mmr_store`,
+// which is expected to be empty
+pub fn create_genesis_block_with_store<
+    H: Hasher<Out = HashOutput>,
+    K: ParaHeadKey,
+    L: BeefyLeaf,
+    S: MmrStore<MMRNode<L>>,
+>(
+    mmr_store: S,
+    new_authority_set: Vec<(Pair, AuthorityId)>,
+    para_id: ParaId,
+    other_para_ids: &[ParaId],
+    rng: &mut StdRng,
+) -> BlockData<H, K, L, S> {
     let (_storage_trie_db, storage_trie_root, chosen_kv_pair, chosen_kv_proof) =
-        generate_random_storage_and_proof();
-    if block_data.is_none() {
-        let genesis_para_header = TestHeader {
+        generate_random_storage_and_proof::<H>(rng, None, None);
+    let genesis_para_header = TestHeader {
+        parent_hash: Default::default(),
+        number: 1,
+        state_root: storage_trie_root,
+        extrinsics_root: Default::default(),
+        digest: Default::default(),
+    };
+    let mut encoded_para_heads = vec![ParaHeadEntry::new::<K>(para_id, &genesis_para_header)];
+
+    let mut other_para_headers = BTreeMap::new();
+    for other_para_id in other_para_ids.iter() {
+        let (_, other_storage_trie_root, _, _) =
+            generate_random_storage_and_proof::<H>(rng, None, None);
+        let other_para_header = TestHeader {
             parent_hash: Default::default(),
             number: 1,
-            state_root: storage_trie_root,
+            state_root: other_storage_trie_root,
             extrinsics_root: Default::default(),
             digest: Default::default(),
         };
-        let encoded_para_heads = vec![(genesis_para_header.hash(), genesis_para_header.encode())];
-
-        let mut memdb = MemoryDB::<KeccakHasher>::default();
-        let mut current_para_heads_merkle_root = Default::default();
-        {
-            let mut trie_db =
-                TrieDBMut::<TrieLayout>::new(&mut memdb, &mut current_para_heads_merkle_root);
-            for (block_hash, para_head) in encoded_para_heads.iter() {
-                trie_db.insert(block_hash.as_ref(), para_head).unwrap();
-            }
-        }
+        encoded_para_heads.push(ParaHeadEntry::new::<K>(*other_para_id, &other_para_header));
+        other_para_headers.insert(*other_para_id, other_para_header);
+    }
 
-        let para_heads_merkle_proof = sp_trie::generate_trie_proof::<TrieLayout, _, _, _>(
-            &memdb,
-            current_para_heads_merkle_root,
-            vec![&genesis_para_header.hash()],
-        )
-        .unwrap();
-
-        // This is root
-        BlockData {
-            chosen_kv_pair,
-            chosen_kv_proof,
-            beefy_mmr_store: MemStore::<MMRNode<LeafData>>::default(),
-            beefy_mmr_leaves: 0,
-            relay_header: TestHeader {
-                parent_hash: Default::default(),
-                number: 1,
-                state_root: Default::default(),
-                extrinsics_root: Default::default(),
-                digest: Default::default(),
-            },
-            para_header: genesis_para_header,
-            encoded_para_head_data: encoded_para_heads,
-            para_header_merkle_proof: para_heads_merkle_proof,
-            signed_commitment: None,
-            current_authority_set: new_authority_set.expect("Genesis needs initial authority set"),
-            current_authority_set_id: 0,
-            para_header_merkle_root: current_para_heads_merkle_root,
-        }
-    } else {
-        if new_authority_set.is_some() && !should_generate_commitment {
-            panic!("We must generate commitment when enacting new authority set");
-        }
+    let (current_para_heads_merkle_root, memdb) = build_para_heads_trie::<H>(&encoded_para_heads);
+    let para_heads_merkle_proof = prove_para_head_inclusion(
+        &memdb,
+        current_para_heads_merkle_root,
+        K::key(para_id, &genesis_para_header),
+    );
 
-        let previous_block_data = block_data.unwrap();
+    let mut genesis_header = TestHeader {
+        parent_hash: Default::default(),
+        number: 1,
+        state_root: Default::default(),
+        extrinsics_root: Default::default(),
+        digest: Default::default(),
+    };
+    genesis_header.digest.push(authority_set_digest_item(
+        0,
+        &new_authority_set
+            .iter()
+            .map(|(_, id)| id.clone())
+            .collect::<Vec<AuthorityId>>(),
+    ));
 
-        let previous_relay_header_hash = previous_block_data.relay_header.hash();
-        let previous_relay_header_number = previous_block_data.relay_header.number;
+    // This is root
+    let genesis = BlockData {
+        chosen_kv_pair,
+        chosen_kv_proof,
+        beefy_mmr_store: mmr_store,
+        beefy_mmr_leaves: 0,
+        relay_header: genesis_header,
+        para_id,
+        para_header: genesis_para_header,
+        other_para_headers,
+        encoded_para_head_data: encoded_para_heads,
+        para_header_merkle_proof: para_heads_merkle_proof,
+        signed_commitment: None,
+        current_authority_set: new_authority_set,
+        current_authority_set_id: 0,
+        para_header_merkle_root: current_para_heads_merkle_root,
+        hasher: PhantomData,
+        para_head_key: PhantomData,
+    };
+    debug_assert_eq!(genesis.validate(), Ok(()));
+    genesis
+}
 
-        let previous_para_header_hash = previous_block_data.para_header.hash();
-        let previous_para_header_number = previous_block_data.para_header.number;
+// Same as `create_random_child_block` but beefy MMR, para heads trie and
+// para storage trie are built using given hasher
+pub fn create_random_child_block_with_hasher<
+    H: Hasher<Out = HashOutput>,
+    K: ParaHeadKey,
+    L: BeefyLeaf,
+>(
+    block_data: Option<&BlockData<H, K, L>>,
+    should_generate_commitment: bool,
+    new_authority_set: Option<Vec<(Pair, AuthorityId)>>,
+    rng: &mut StdRng,
+) -> BlockData<H, K, L> {
+    match block_data {
+        Some(previous_block_data) => create_random_child_block_with_store(
+            previous_block_data,
+            should_generate_commitment,
+            new_authority_set,
+            rng,
+        ),
+        None => create_genesis_block_with_paras(
+            new_authority_set.expect("Genesis needs initial authority set"),
+            DEFAULT_PARA_ID,
+            &[],
+            rng,
+        ),
+    }
+}
 
-        let new_para_header = TestHeader {
-            parent_hash: previous_para_header_hash,
-            number: previous_para_header_number + 1,
-            state_root: storage_trie_root,
+// Child of `previous_block_data` whose beefy MMR is appended to the same kind of store
+pub fn create_random_child_block_with_store<
+    H: Hasher<Out = HashOutput>,
+    K: ParaHeadKey,
+    L: BeefyLeaf,
+    S: MmrStore<MMRNode<L>>,
+>(
+    previous_block_data: &BlockData<H, K, L, S>,
+    should_generate_commitment: bool,
+    new_authority_set: Option<Vec<(Pair, AuthorityId)>>,
+    rng: &mut StdRng,
+) -> BlockData<H, K, L, S> {
+    create_random_child_block_with_scheme(
+        previous_block_data,
+        should_generate_commitment,
+        new_authority_set,
+        SigningScheme::Raw,
+        rng,
+    )
+}
+
+// Same as `create_random_child_block_with_store` but commitment is signed
+// using given scheme, e.g. `SigningScheme::Keccak256` for EVM light clients
+pub fn create_random_child_block_with_scheme<
+    H: Hasher<Out = HashOutput>,
+    K: ParaHeadKey,
+    L: BeefyLeaf,
+    S: MmrStore<MMRNode<L>>,
+>(
+    previous_block_data: &BlockData<H, K, L, S>,
+    should_generate_commitment: bool,
+    new_authority_set: Option<Vec<(Pair, AuthorityId)>>,
+    signing_scheme: SigningScheme,
+    rng: &mut StdRng,
+) -> BlockData<H, K, L, S> {
+    let (_storage_trie_db, storage_trie_root, chosen_kv_pair, chosen_kv_proof) =
+        generate_random_storage_and_proof::<H>(rng, None, None);
+    let block = create_child_block_with_storage(
+        previous_block_data,
+        should_generate_commitment,
+        new_authority_set,
+        signing_scheme,
+        (storage_trie_root, chosen_kv_pair, chosen_kv_proof),
+        rng,
+    );
+    // Catches proof generation bugs early instead of failing later in claim verification
+    debug_assert_eq!(block.validate(), Ok(()));
+    block
+}
+
+// Same as `create_random_child_block_with_scheme` but para block of the child has
+// given storage, i.e. its state root along with the chosen kv pair and its proof.
+// Storage is taken as is, so that it may be built using a different hasher than `H`.
+pub fn create_child_block_with_storage<
+    H: Hasher<Out = HashOutput>,
+    K: ParaHeadKey,
+    L: BeefyLeaf,
+    S: MmrStore<MMRNode<L>>,
+>(
+    previous_block_data: &BlockData<H, K, L, S>,
+    should_generate_commitment: bool,
+    new_authority_set: Option<Vec<(Pair, AuthorityId)>>,
+    signing_scheme: SigningScheme,
+    storage: (HashOutput, (Vec<u8>, Vec<u8>), Vec<Vec<u8>>),
+    rng: &mut StdRng,
+) -> BlockData<H, K, L, S> {
+    let (storage_trie_root, chosen_kv_pair, chosen_kv_proof) = storage;
+    if new_authority_set.is_some() && !should_generate_commitment {
+        panic!("We must generate commitment when enacting new authority set");
+    }
+
+    let previous_relay_header_hash = previous_block_data.relay_header.hash();
+    let previous_relay_header_number = previous_block_data.relay_header.number;
+
+    let previous_para_header_hash = previous_block_data.para_header.hash();
+    let previous_para_header_number = previous_block_data.para_header.number;
+
+    let new_para_header = TestHeader {
+        parent_hash: previous_para_header_hash,
+        number: previous_para_header_number + 1,
+        state_root: storage_trie_root,
+        extrinsics_root: Default::default(),
+        digest: Default::default(),
+    };
+
+    let mut encoded_para_heads = previous_block_data.encoded_para_head_data.clone();
+    // Update encoded para head to include current block here
+    // We are deliberately doing this before trie root calculation
+    // to mimic the real setup
+    encoded_para_heads.push(ParaHeadEntry::new::<K>(
+        previous_block_data.para_id,
+        &new_para_header,
+    ));
+
+    // Other parachains advance along with the tracked one
+    let mut other_para_headers = BTreeMap::new();
+    for (other_para_id, previous_other_para_header) in previous_block_data.other_para_headers.iter()
+    {
+        let (_, other_storage_trie_root, _, _) =
+            generate_random_storage_and_proof::<H>(rng, None, None);
+        let other_para_header = TestHeader {
+            parent_hash: previous_other_para_header.hash(),
+            number: previous_other_para_header.number + 1,
+            state_root: other_storage_trie_root,
             extrinsics_root: Default::default(),
             digest: Default::default(),
         };
+        encoded_para_heads.push(ParaHeadEntry::new::<K>(*other_para_id, &other_para_header));
+        other_para_headers.insert(*other_para_id, other_para_header);
+    }
 
-        let mut encoded_para_heads = previous_block_data.encoded_para_head_data.clone();
-        // Update encoded para head to include current block here
-        // We are deliberately doing this before trie root calculation
-        // to mimic the real setup
-        encoded_para_heads.push((new_para_header.hash(), new_para_header.encode()));
-
-        let mut memdb = MemoryDB::<KeccakHasher>::default();
-        let mut previous_para_heads_merkle_root = Default::default();
-        {
-            let mut trie_db =
-                TrieDBMut::<TrieLayout>::new(&mut memdb, &mut previous_para_heads_merkle_root);
-            for (block_hash, para_head) in encoded_para_heads.iter() {
-                trie_db.insert(block_hash.as_ref(), para_head).unwrap();
-            }
-        }
+    let (previous_para_heads_merkle_root, memdb) = build_para_heads_trie::<H>(&encoded_para_heads);
+    let para_heads_merkle_proof = prove_para_head_inclusion(
+        &memdb,
+        previous_para_heads_merkle_root,
+        K::key(previous_block_data.para_id, &new_para_header),
+    );
 
-        let para_heads_merkle_proof = sp_trie::generate_trie_proof::<TrieLayout, _, _, _>(
-            &memdb,
+    let mut mmr_appender = MmrAppender::<L, H, S>::new(
+        previous_block_data.beefy_mmr_store.clone(),
+        previous_block_data.beefy_mmr_leaves,
+    );
+    // Every relay header announces its authority set, so leaf of previous block
+    // carries the set which signs commitments after it
+    let mmr_root = mmr_appender.append(
+        L::from_relay_block(
+            &previous_block_data.relay_header,
             previous_para_heads_merkle_root,
-            vec![&new_para_header.hash()],
         )
-        .unwrap();
+        .expect("Relay header must announce its authority set"),
+    );
+
+    let mut new_header = TestHeader {
+        parent_hash: previous_relay_header_hash,
+        number: previous_relay_header_number + 1,
+        state_root: Default::default(),
+        extrinsics_root: Default::default(),
+        digest: Default::default(),
+    };
+    new_header.digest.push(mmr_root_digest_item(&mmr_root));
+    match new_authority_set.as_ref() {
+        Some(new_authority_set) => new_header.digest.push(authority_set_digest_item(
+            previous_block_data.current_authority_set_id + 1,
+            &new_authority_set
+                .iter()
+                .map(|(_, id)| id.clone())
+                .collect::<Vec<AuthorityId>>(),
+        )),
+        None => new_header.digest.push(authority_set_digest_item(
+            previous_block_data.current_authority_set_id,
+            &previous_block_data
+                .current_authority_set
+                .iter()
+                .map(|(_, id)| id.clone())
+                .collect::<Vec<AuthorityId>>(),
+        )),
+    }
 
-        let mut mem_mmr = MemMMR::<_, MergeStrategy<LeafData, HashingAlgo>>::new(
-            mmr_size_from_number_of_leaves(previous_block_data.beefy_mmr_leaves),
-            previous_block_data.beefy_mmr_store.clone(),
+    let maybe_signed_commitment = if should_generate_commitment {
+        let signed_commitment = if new_authority_set.is_none() {
+            generate_signed_commitment(
+                previous_block_data.current_authority_set_id,
+                previous_relay_header_number + 1,
+                CommitmentPayload::new(
+                    mmr_root,
+                    None,
+                    previous_block_data.current_authority_set_id.into(),
+                ),
+                previous_block_data
+                    .current_authority_set
+                    .iter()
+                    .map(|(p, _)| p.clone())
+                    .collect::<Vec<Pair>>()
+                    .as_ref(),
+                &[],
+                signing_scheme,
+            )
+        } else {
+            let new_authority_set = new_authority_set.clone().unwrap();
+            generate_signed_commitment(
+                previous_block_data.current_authority_set_id,
+                previous_relay_header_number + 1,
+                CommitmentPayload::new(
+                    mmr_root,
+                    Some(new_authority_set.iter().map(|(_, id)| id.clone()).collect()),
+                    ValidatorSetId(previous_block_data.current_authority_set_id + 1),
+                ),
+                previous_block_data
+                    .current_authority_set
+                    .iter()
+                    .map(|(p, _)| p.clone())
+                    .collect::<Vec<Pair>>()
+                    .as_ref(),
+                &[],
+                signing_scheme,
+            )
+        };
+
+        Some(signed_commitment)
+    } else {
+        None
+    };
+
+    BlockData {
+        chosen_kv_pair,
+        chosen_kv_proof,
+        beefy_mmr_leaves: mmr_appender.leaves(),
+        beefy_mmr_store: mmr_appender.into_store(),
+        relay_header: new_header,
+        signed_commitment: maybe_signed_commitment,
+        current_authority_set_id: if new_authority_set.is_none() {
+            previous_block_data.current_authority_set_id
+        } else {
+            previous_block_data.current_authority_set_id + 1
+        },
+        current_authority_set: if new_authority_set.is_none() {
+            previous_block_data.current_authority_set.clone()
+        } else {
+            new_authority_set.unwrap()
+        },
+
+        para_id: previous_block_data.para_id,
+        para_header: new_para_header,
+        other_para_headers,
+        encoded_para_head_data: encoded_para_heads,
+        para_header_merkle_proof: para_heads_merkle_proof,
+        para_header_merkle_root: previous_para_heads_merkle_root,
+        hasher: PhantomData,
+        para_head_key: PhantomData,
+    }
+}
+
+// Declarative way of building chains, e.g. chain with handovers at blocks 3 and 7:
+// ChainBuilder::new(set_0)
+//     .push_block()
+//     .rotate_authorities(set_1)
+//     .push_block_with_commitment()
+//     ...
+pub struct ChainBuilder {
+    blocks: Vec<BlockData>,
+    // Authority set to be enacted by the next block with commitment
+    pending_authority_set: Option<Vec<(Pair, AuthorityId)>>,
+    signing_scheme: SigningScheme,
+    rng: StdRng,
+}
+
+impl ChainBuilder {
+    // Starts a chain with genesis block using given authority set
+    pub fn new(initial_authority_set: Vec<(Pair, AuthorityId)>) -> Self {
+        Self::with_rng(initial_authority_set, StdRng::from_entropy())
+    }
+
+    pub fn with_seed(initial_authority_set: Vec<(Pair, AuthorityId)>, seed: u64) -> Self {
+        Self::with_rng(initial_authority_set, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(initial_authority_set: Vec<(Pair, AuthorityId)>, mut rng: StdRng) -> Self {
+        let genesis =
+            create_random_child_block_with_rng(None, false, Some(initial_authority_set), &mut rng);
+        Self {
+            blocks: vec![genesis],
+            pending_authority_set: None,
+            signing_scheme: SigningScheme::Raw,
+            rng,
+        }
+    }
+
+    // Commitments of blocks pushed from now on are signed using given scheme
+    pub fn with_signing_scheme(mut self, signing_scheme: SigningScheme) -> Self {
+        self.signing_scheme = signing_scheme;
+        self
+    }
+
+    pub fn push_block(self) -> Self {
+        if self.pending_authority_set.is_some() {
+            panic!("Authority set rotation must be followed by a block with commitment");
+        }
+        self.push(false)
+    }
+
+    // Enacts pending authority set rotation if there is one
+    pub fn push_block_with_commitment(self) -> Self {
+        self.push(true)
+    }
+
+    pub fn rotate_authorities(mut self, new_authority_set: Vec<(Pair, AuthorityId)>) -> Self {
+        if self.pending_authority_set.is_some() {
+            panic!("Previous authority set rotation is not enacted yet");
+        }
+        self.pending_authority_set = Some(new_authority_set);
+        self
+    }
+
+    pub fn build(self) -> Vec<BlockData> {
+        if self.pending_authority_set.is_some() {
+            panic!("Authority set rotation is not enacted by any block");
+        }
+        self.blocks
+    }
+
+    // Authority set seeded from the builder's rng, so that seeded chains stay reproducible
+    fn fresh_authority_set(&mut self, number: usize) -> Vec<(Pair, AuthorityId)> {
+        generate_beefy_pairs_from_seed(number, self.rng.gen())
+    }
+
+    fn push(mut self, should_generate_commitment: bool) -> Self {
+        let block = create_random_child_block_with_scheme(
+            self.blocks.last().expect("Chain starts with genesis block"),
+            should_generate_commitment,
+            self.pending_authority_set.take(),
+            self.signing_scheme,
+            &mut self.rng,
         );
+        self.blocks.push(block);
+        self
+    }
+}
 
-        mem_mmr
-            .push(MMRNode::Data((
-                previous_relay_header_number,
-                previous_relay_header_hash,
-                previous_para_heads_merkle_root,
-            )))
-            .unwrap();
+// Chain of `length` blocks (genesis included) in which every `commit_every`-th block
+// carries a signed commitment. Blocks at positions in `handovers_at` enact a fresh
+// authority set of the same size, so they carry a commitment as well.
+// Same seed gives the same chain.
+pub fn create_chain_with_commitment_interval(
+    length: usize,
+    commit_every: usize,
+    handovers_at: &[usize],
+    seed: u64,
+) -> Vec<BlockData> {
+    assert!(commit_every > 0, "Commitment interval must not be zero");
 
-        let new_header = TestHeader {
-            parent_hash: previous_relay_header_hash,
-            number: previous_relay_header_number + 1,
-            state_root: Default::default(),
-            extrinsics_root: Default::default(),
-            digest: Default::default(),
+    let mut chain = ChainBuilder::with_seed(
+        generate_beefy_pairs_from_seed(AUTHORITY_SET_SIZE, seed),
+        seed,
+    );
+    for position in 1..length {
+        chain = if handovers_at.contains(&position) {
+            let next_authority_set = chain.fresh_authority_set(AUTHORITY_SET_SIZE);
+            chain
+                .rotate_authorities(next_authority_set)
+                .push_block_with_commitment()
+        } else if position % commit_every == 0 {
+            chain.push_block_with_commitment()
+        } else {
+            chain.push_block()
         };
+    }
+    chain.build()
+}
 
-        let maybe_signed_commitment = if should_generate_commitment {
-            let mmr_root = mem_mmr.get_root().unwrap();
-            let signed_commitment = if new_authority_set.is_none() {
-                generate_signed_commitment(
-                    previous_block_data.current_authority_set_id,
-                    previous_relay_header_number + 1,
-                    CommitmentPayload {
-                        mmr_node: mmr_root,
-                        changed_authority_ids: None,
-                        new_validator_set_id: previous_block_data.current_authority_set_id,
-                    },
-                    previous_block_data
-                        .current_authority_set
-                        .iter()
-                        .map(|(p, _)| p.clone())
-                        .collect::<Vec<Pair>>()
-                        .as_ref(),
+const AUTHORITY_SET_SIZE: usize = 5;
+
+// Blocks of a `BlockStream`, all of them share the same beefy MMR store
+pub type SharedStoreBlockData =
+    BlockData<HashingAlgo, HashKeyed, LeafData, Rc<MemStore<MMRNode<LeafData>>>>;
+
+// Endless chain of random blocks, every `commitment_period`-th block carries a signed
+// commitment. Only the last block is kept, so blocks dropped by the caller are freed.
+// Every block appends its leaf to the shared MMR store instead of copying it.
+// Para heads trie of a streamed block only holds heads of the block and its parent,
+// which is enough to prove the parent's para head, instead of every head so far.
+pub struct BlockStream {
+    last_block: Option<SharedStoreBlockData>,
+    initial_authority_set: Option<Vec<(Pair, AuthorityId)>>,
+    commitment_period: u64,
+    rng: StdRng,
+}
+
+impl BlockStream {
+    pub fn new(
+        initial_authority_set: Vec<(Pair, AuthorityId)>,
+        commitment_period: u64,
+        seed: u64,
+    ) -> Self {
+        assert!(commitment_period > 0, "Commitment period must not be zero");
+        Self {
+            last_block: None,
+            initial_authority_set: Some(initial_authority_set),
+            commitment_period,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Iterator for BlockStream {
+    type Item = SharedStoreBlockData;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let block = match self.last_block.as_ref() {
+            None => create_genesis_block_with_store(
+                Rc::new(MemStore::default()),
+                self.initial_authority_set
+                    .take()
+                    .expect("Genesis needs initial authority set"),
+                DEFAULT_PARA_ID,
+                &[],
+                &mut self.rng,
+            ),
+            Some(last_block) => create_random_child_block_with_store(
+                last_block,
+                (last_block.relay_header.number + 1) % self.commitment_period == 0,
+                None,
+                &mut self.rng,
+            ),
+        };
+        // Child only needs para heads added by this block
+        let mut last_block = block.clone();
+        let heads_per_block = 1 + last_block.other_para_headers.len();
+        let older_heads = last_block.encoded_para_head_data.len() - heads_per_block;
+        last_block.encoded_para_head_data.drain(..older_heads);
+        self.last_block = Some(last_block);
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ethereum_actor::EthereumActor;
+    use crate::types::{BlockNumber, SignerProof, ValidatorSetId};
+    use crate::utils::{compute_mmr_root, generate_signer_proofs, leaf_for_relay_block};
+
+    #[test]
+    fn stream_blocks_share_mmr_store() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let authority_ids = authorities
+            .iter()
+            .map(|(_, id)| id.clone())
+            .collect::<Vec<_>>();
+        let blocks = BlockStream::new(authorities, 4, 0)
+            .take(4)
+            .collect::<Vec<_>>();
+        assert!(blocks
+            .iter()
+            .all(|block| Rc::ptr_eq(&block.beefy_mmr_store, &blocks[0].beefy_mmr_store)));
+
+        // Leaves appended by later blocks do not change MMRs of earlier ones
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                blocks[3].ethereum_view(),
+                generate_signer_proofs(&authority_ids, &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+        let claim_proof = blocks[1].build_claim_proof(&blocks[2], &blocks[3]);
+        assert_eq!(actor.verify_claim_proof(None, &claim_proof), Ok(()));
+        assert_eq!(
+            blocks[2].ethereum_view().beefy_mmr_root,
+            compute_mmr_root::<HashingAlgo>(&[
+                leaf_for_relay_block(&blocks[0].relay_header, blocks[1].para_header_merkle_root)
+                    .unwrap(),
+                leaf_for_relay_block(&blocks[1].relay_header, blocks[2].para_header_merkle_root)
+                    .unwrap(),
+            ])
+        );
+    }
+
+    #[test]
+    fn stream_state_stays_flat_over_long_chain() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let authority_ids = authorities
+            .iter()
+            .map(|(_, id)| id.clone())
+            .collect::<Vec<_>>();
+        let mut stream = BlockStream::new(authorities, 1000, 0);
+        let genesis = stream.next().unwrap();
+        let mut actor = EthereumActor::from_genesis(&genesis, 10, None);
+
+        let mut last_block = genesis;
+        for block in stream.take(50_000) {
+            // Para heads of the block and its parent, no matter how long the chain is
+            assert_eq!(block.encoded_para_head_data.len(), 2);
+            if block.signed_commitment.is_some() {
+                actor
+                    .ingest_new_header(
+                        block.ethereum_view(),
+                        generate_signer_proofs(&authority_ids, &[0, 1, 2, 3, 4]),
+                    )
+                    .unwrap();
+            }
+            last_block = block;
+        }
+        assert_eq!(last_block.relay_header.number, 50_001);
+        assert_eq!(actor.last_finalized_number(), Some(BlockNumber(50_000)));
+    }
+
+    fn signer_proofs(block: &BlockData) -> Vec<SignerProof> {
+        let authority_ids = block
+            .current_authority_set
+            .iter()
+            .map(|(_, id)| id.clone())
+            .collect::<Vec<_>>();
+        generate_signer_proofs(&authority_ids, &[0, 1, 2, 3, 4])
+    }
+
+    #[test]
+    fn chain_is_followed_across_unsigned_stretches() {
+        let blocks = create_chain_with_commitment_interval(100, 10, &[55], 0);
+        let committed = blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| block.signed_commitment.is_some())
+            .map(|(position, _)| position)
+            .collect::<Vec<usize>>();
+        assert_eq!(blocks.len(), 100);
+        assert_eq!(committed, vec![10, 20, 30, 40, 50, 55, 60, 70, 80, 90]);
+
+        // Handover commitment is signed by the outgoing set
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        for position in committed {
+            let signers = if position <= 55 { 0 } else { 55 };
+            actor
+                .ingest_new_header(
+                    blocks[position].ethereum_view(),
+                    signer_proofs(&blocks[signers]),
                 )
-            } else {
-                let new_authority_set = new_authority_set.clone().unwrap();
-                generate_signed_commitment(
-                    previous_block_data.current_authority_set_id,
-                    previous_relay_header_number + 1,
-                    CommitmentPayload {
-                        mmr_node: mmr_root,
-                        changed_authority_ids: Some(
-                            new_authority_set.iter().map(|(_, id)| id.clone()).collect(),
-                        ),
-                        new_validator_set_id: previous_block_data.current_authority_set_id + 1,
-                    },
-                    previous_block_data
-                        .current_authority_set
+                .unwrap();
+        }
+        assert_eq!(actor.current_set_id(), ValidatorSetId(1));
+        let claim_proof = blocks[1].build_claim_proof(&blocks[2], &blocks[90]);
+        assert_eq!(actor.verify_claim_proof(None, &claim_proof), Ok(()));
+    }
+
+    #[test]
+    fn same_seed_gives_same_chain() {
+        let hashes = |seed| {
+            create_chain_with_commitment_interval(20, 10, &[15], seed)
+                .iter()
+                .map(|block| block.relay_header.hash())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(hashes(0), hashes(0));
+        assert_ne!(hashes(0), hashes(1));
+    }
+
+    // Everything a seeded chain is made of, as bytes
+    fn encoded_chain(seed: u64) -> Vec<Vec<u8>> {
+        ChainBuilder::with_seed(generate_beefy_pairs_from_seed(5, 0), seed)
+            .push_block()
+            .push_block_with_commitment()
+            .rotate_authorities(generate_beefy_pairs_from_seed(5, 1))
+            .push_block_with_commitment()
+            .build()
+            .iter()
+            .map(|block| {
+                (
+                    &block.relay_header,
+                    &block.para_header,
+                    &block.signed_commitment,
+                    block.ethereum_view().beefy_mmr_root,
+                    block
+                        .encoded_para_head_data
                         .iter()
-                        .map(|(p, _)| p.clone())
-                        .collect::<Vec<Pair>>()
-                        .as_ref(),
+                        .map(|entry| (&entry.key, &entry.encoded_header))
+                        .collect::<Vec<_>>(),
+                    &block.para_header_merkle_proof,
+                    &block.chosen_kv_pair,
+                    &block.chosen_kv_proof,
                 )
-            };
+                    .encode()
+            })
+            .collect()
+    }
 
-            Some(signed_commitment)
-        } else {
-            None
-        };
+    #[test]
+    fn chains_built_from_same_seed_are_identical() {
+        assert_eq!(encoded_chain(7), encoded_chain(7));
+        assert_ne!(encoded_chain(7), encoded_chain(8));
+    }
 
-        BlockData {
-            chosen_kv_pair,
-            chosen_kv_proof,
-            beefy_mmr_store: mem_mmr.store().clone(),
-            beefy_mmr_leaves: previous_block_data.beefy_mmr_leaves + 1,
-            relay_header: new_header,
-            signed_commitment: maybe_signed_commitment,
-            current_authority_set_id: if new_authority_set.is_none() {
-                previous_block_data.current_authority_set_id
-            } else {
-                previous_block_data.current_authority_set_id + 1
-            },
-            current_authority_set: if new_authority_set.is_none() {
-                previous_block_data.current_authority_set.clone()
-            } else {
-                new_authority_set.unwrap()
-            },
+    #[test]
+    #[should_panic(expected = "Commitment interval must not be zero")]
+    fn zero_commitment_interval_is_rejected() {
+        create_chain_with_commitment_interval(4, 0, &[], 0);
+    }
+
+    #[test]
+    fn reference_mmr_root_matches_ten_block_chain() {
+        let mut chain = ChainBuilder::with_seed(generate_beefy_pairs_from_seed(5, 0), 0);
+        for _ in 0..9 {
+            chain = chain.push_block();
+        }
+        let blocks = chain.build();
+        let leaves = blocks
+            .windows(2)
+            .map(|pair| {
+                leaf_for_relay_block(&pair[0].relay_header, pair[1].para_header_merkle_root)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
 
-            para_header: new_para_header,
-            encoded_para_head_data: encoded_para_heads,
-            para_header_merkle_proof: para_heads_merkle_proof,
-            para_header_merkle_root: previous_para_heads_merkle_root,
+        assert_eq!(blocks.len(), 10);
+        // Every block commits to the MMR of the leaves of its ancestors
+        for (number_of_leaves, block) in blocks.iter().enumerate().skip(1) {
+            assert_eq!(
+                block.ethereum_view().beefy_mmr_root,
+                compute_mmr_root::<HashingAlgo>(&leaves[..number_of_leaves])
+            );
         }
     }
 }
@@ -0,0 +1,24 @@
+use crate::mmr::MMRNode;
+use crate::traits::BeefyLeaf;
+use crate::types::{HashOutput, LeafData, MmrPosition, ParaId, TestHeader};
+use alloc::vec::Vec;
+use codec::{Decode, Encode};
+
+// Everything needed to prove that a key value pair exists in storage of a finalized
+// para block, built by `BlockData::build_claim_proof`
+#[derive(Clone, PartialEq, Debug, Encode, Decode)]
+pub struct ClaimProof<L: BeefyLeaf = LeafData> {
+    // Relay block and proof of its leaf in finalized MMR
+    pub relay_header: TestHeader,
+    pub mmr_proof_items: Vec<MMRNode<L>>,
+    pub block_pos_in_mmr: MmrPosition,
+
+    pub para_id: ParaId,
+    pub para_header: TestHeader,
+    // Para heads merkle root committed in MMR leaf of the relay block
+    pub para_header_merkle_root: HashOutput,
+    pub para_header_inclusion_proof: Vec<Vec<u8>>,
+
+    pub claimed_kv: (Vec<u8>, Vec<u8>),
+    pub kv_proof: Vec<Vec<u8>>,
+}
@@ -0,0 +1,447 @@
+use crate::error::VerificationError;
+use crate::mmr::MMRNode;
+use crate::traits::Hashable;
+use crate::types::{HashOutput, SignerProof, TrieLayout, ValidatorSetId};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use beefy_primitives::crypto::{AuthorityId, AuthoritySignature};
+use beefy_primitives::{Commitment, SignedCommitment};
+use codec::{Decode, Encode};
+use sp_core::ecdsa;
+use sp_core::hashing::keccak_256;
+use sp_core::sp_std::marker::PhantomData;
+use sp_runtime::RuntimeAppPublic;
+
+pub type PayloadId = [u8; 2];
+
+// Id of the payload item carrying SCALE encoded MMR root
+pub const MMR_ROOT_ID: PayloadId = *b"mh";
+
+#[derive(Clone, PartialEq, Encode, Decode)]
+pub struct CommitmentPayload<Leaf: Hashable + Encode + Decode> {
+    // Payload items sorted by id, like in BEEFY payload
+    pub(crate) items: Vec<(PayloadId, Vec<u8>)>,
+    pub changed_authority_ids: Option<Vec<AuthorityId>>,
+    pub new_validator_set_id: ValidatorSetId,
+    pub(crate) leaf: PhantomData<Leaf>,
+}
+
+impl<Leaf: Hashable + Encode + Decode> CommitmentPayload<Leaf> {
+    pub fn new(
+        mmr_root: MMRNode<Leaf>,
+        changed_authority_ids: Option<Vec<AuthorityId>>,
+        new_validator_set_id: ValidatorSetId,
+    ) -> Self {
+        Self {
+            items: vec![(MMR_ROOT_ID, mmr_root.encode())],
+            changed_authority_ids,
+            new_validator_set_id,
+            leaf: PhantomData,
+        }
+    }
+
+    // Adds an opaque payload item, replacing existing item with the same id
+    pub fn with_extra(mut self, id: PayloadId, data: Vec<u8>) -> Self {
+        match self
+            .items
+            .binary_search_by_key(&id, |(item_id, _)| *item_id)
+        {
+            Ok(index) => self.items[index].1 = data,
+            Err(index) => self.items.insert(index, (id, data)),
+        }
+        self
+    }
+
+    // None if there is no MMR root item or it cannot be decoded
+    pub fn mmr_root(&self) -> Option<MMRNode<Leaf>> {
+        self.get_extra(MMR_ROOT_ID)
+            .and_then(|data| MMRNode::<Leaf>::decode(&mut &data[..]).ok())
+    }
+
+    // Chains running several MMRs commit to each root under its own id. Unlike the
+    // beefy MMR, whose leaf count the view carries, the leaf count is committed too.
+    pub fn with_mmr_root(self, id: PayloadId, mmr_root: MMRNode<Leaf>, mmr_leaves: u64) -> Self {
+        self.with_extra(id, (mmr_root, mmr_leaves).encode())
+    }
+
+    // Root and leaf count of an MMR added with `with_mmr_root`, None if there is
+    // no such item or it cannot be decoded
+    pub fn mmr_root_by_id(&self, id: PayloadId) -> Option<(MMRNode<Leaf>, u64)> {
+        self.get_extra(id)
+            .and_then(|data| <(MMRNode<Leaf>, u64)>::decode(&mut &data[..]).ok())
+    }
+
+    pub fn get_extra(&self, id: PayloadId) -> Option<&[u8]> {
+        self.items
+            .binary_search_by_key(&id, |(item_id, _)| *item_id)
+            .ok()
+            .map(|index| self.items[index].1.as_slice())
+    }
+}
+
+impl<Leaf: Hashable<Out = HashOutput> + Encode + Decode> CommitmentPayload<Leaf> {
+    // Payload of a commitment from a running node, which is SCALE encoded sorted
+    // (id, data) items with bare MMR root hash under `mh`. Payload does not announce
+    // handovers, so it is taken as signed by (and enacting) `validator_set_id`.
+    pub fn from_beefy_payload(
+        encoded_payload: &[u8],
+        validator_set_id: ValidatorSetId,
+    ) -> Result<Self, codec::Error> {
+        let items = Vec::<(PayloadId, Vec<u8>)>::decode(&mut &encoded_payload[..])?;
+        let mmr_root = items
+            .iter()
+            .find(|(id, _)| *id == MMR_ROOT_ID)
+            .ok_or_else(|| codec::Error::from("Payload does not contain MMR root"))
+            .and_then(|(_, data)| HashOutput::decode(&mut &data[..]))?;
+
+        Ok(items.into_iter().filter(|(id, _)| *id != MMR_ROOT_ID).fold(
+            Self::new(MMRNode::Hash(mmr_root), None, validator_set_id),
+            |payload, (id, data)| payload.with_extra(id, data),
+        ))
+    }
+}
+
+// What authorities sign. EVM light clients verify keccak256 of the encoded
+// commitment, since the signer can then be recovered cheaply with `ecrecover`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SigningScheme {
+    // Encoded commitment is signed as is (ecdsa pair hashes it with blake2)
+    Raw,
+    Keccak256,
+}
+
+pub fn verify_commitment_signature(
+    authority_id: &AuthorityId,
+    signature: &AuthoritySignature,
+    encoded_commitment: &[u8],
+    signing_scheme: SigningScheme,
+) -> bool {
+    match signing_scheme {
+        SigningScheme::Raw => authority_id.verify(&encoded_commitment, signature),
+        // Same as `ecrecover` on EVM
+        SigningScheme::Keccak256 => AsRef::<ecdsa::Signature>::as_ref(signature)
+            .recover_prehashed(&keccak_256(encoded_commitment))
+            .map_or(false, |signer| {
+                &signer == AsRef::<ecdsa::Public>::as_ref(authority_id)
+            }),
+    }
+}
+
+// Signers with their signatures, proven by a single multiproof over the authority
+// merkle tree. Relayer sends it as one SCALE encoded blob.
+#[derive(Clone, PartialEq, Debug, Encode, Decode)]
+pub struct SignerMultiproof {
+    pub signers: Vec<(u32, AuthorityId, AuthoritySignature)>,
+    pub proof: Vec<Vec<u8>>,
+}
+
+// Number of signatures required to consider commitment valid (2/3 + 1 of the set)
+pub fn signature_threshold(number_of_authorities: usize) -> usize {
+    2 * number_of_authorities / 3 + 1
+}
+
+// Verifies signatures of signers who proved their membership in the authority merkle root
+pub fn verify_signed_commitment_with_proofs<TBlockNumber: Encode, TPayload: Encode>(
+    signed_commitment: &SignedCommitment<TBlockNumber, TPayload>,
+    authority_root: HashOutput,
+    authority_set_len: u32,
+    signer_proofs: &[SignerProof],
+    signing_scheme: SigningScheme,
+) -> Result<Vec<AuthorityId>, VerificationError> {
+    if signed_commitment.signatures.len() != authority_set_len as usize {
+        return Err(VerificationError::SignatureCountMismatch {
+            expected: authority_set_len as usize,
+            got: signed_commitment.signatures.len(),
+        });
+    }
+
+    // Malformed submissions are rejected before any proof or signature is verified.
+    // Signers are keyed by their index so that same signer cannot be counted twice.
+    let mut submitted = BTreeMap::new();
+    for (index, authority_id, _) in signer_proofs.iter() {
+        if *index >= authority_set_len {
+            return Err(VerificationError::AuthorityProofInvalid { index: *index });
+        }
+        if submitted.values().any(|id| id == &authority_id)
+            || submitted.insert(*index, authority_id).is_some()
+        {
+            return Err(VerificationError::DuplicateSigner { index: *index });
+        }
+    }
+
+    let encoded_commitment = signed_commitment.commitment.encode();
+    // Every proof and signature is verified before deciding, so that result (and time
+    // taken) does not depend on position of the invalid one
+    let mut invalid_proof = None;
+    let mut invalid_signatures = 0;
+    // Signers whose signature verified
+    let mut signers = BTreeMap::new();
+    for (index, authority_id, proof) in signer_proofs.iter() {
+        let items = vec![(index.encode(), Some(authority_id.encode()))];
+        let is_member = sp_trie::verify_trie_proof::<TrieLayout, _, _, _>(
+            &authority_root,
+            &*proof,
+            items.iter(),
+        )
+        .is_ok();
+        let is_signed = match &signed_commitment.signatures[*index as usize] {
+            Some(signature) => verify_commitment_signature(
+                authority_id,
+                signature,
+                &encoded_commitment,
+                signing_scheme,
+            ),
+            None => false,
+        };
+
+        if !is_member {
+            invalid_proof = invalid_proof.or(Some(*index));
+        } else if !is_signed {
+            invalid_signatures += 1;
+        } else {
+            signers.insert(*index, authority_id.clone());
+        }
+    }
+
+    if let Some(index) = invalid_proof {
+        return Err(VerificationError::AuthorityProofInvalid { index });
+    }
+    if invalid_signatures > 0 {
+        return Err(VerificationError::SignatureInvalid);
+    }
+    let required = signature_threshold(authority_set_len as usize);
+    if signers.len() < required {
+        return Err(VerificationError::NotEnoughSignatures {
+            required,
+            got: signers.len(),
+        });
+    }
+    Ok(signers.into_iter().map(|(_, id)| id).collect())
+}
+
+// Same as `verify_signed_commitment_with_proofs` but signers and their signatures
+// come from SCALE encoded `SignerMultiproof`
+pub fn verify_commitment_with_signer_multiproof<TBlockNumber: Encode, TPayload: Encode>(
+    commitment: &Commitment<TBlockNumber, TPayload>,
+    authority_root: HashOutput,
+    authority_set_len: u32,
+    encoded_signer_multiproof: &[u8],
+    signing_scheme: SigningScheme,
+) -> Result<Vec<AuthorityId>, VerificationError> {
+    let signer_multiproof = SignerMultiproof::decode(&mut &encoded_signer_multiproof[..])
+        .map_err(|_| VerificationError::DecodeFailed)?;
+
+    let mut signers = BTreeMap::new();
+    for (index, authority_id, _) in signer_multiproof.signers.iter() {
+        if *index >= authority_set_len {
+            return Err(VerificationError::AuthorityProofInvalid { index: *index });
+        }
+        if signers.values().any(|id| id == authority_id)
+            || signers.insert(*index, authority_id.clone()).is_some()
+        {
+            return Err(VerificationError::DuplicateSigner { index: *index });
+        }
+    }
+
+    // Whole batch is rejected if any of the signers is not part of the set
+    let items = signers
+        .iter()
+        .map(|(index, authority_id)| (index.encode(), Some(authority_id.encode())))
+        .collect::<Vec<_>>();
+    if sp_trie::verify_trie_proof::<TrieLayout, _, _, _>(
+        &authority_root,
+        &*signer_multiproof.proof,
+        items.iter(),
+    )
+    .is_err()
+    {
+        return Err(VerificationError::SignerMultiproofInvalid);
+    }
+
+    let encoded_commitment = commitment.encode();
+    let invalid_signatures = signer_multiproof
+        .signers
+        .iter()
+        .filter(|(_, authority_id, signature)| {
+            !verify_commitment_signature(
+                authority_id,
+                signature,
+                &encoded_commitment,
+                signing_scheme,
+            )
+        })
+        .count();
+    if invalid_signatures > 0 {
+        return Err(VerificationError::SignatureInvalid);
+    }
+
+    let required = signature_threshold(authority_set_len as usize);
+    if signers.len() < required {
+        return Err(VerificationError::NotEnoughSignatures {
+            required,
+            got: signers.len(),
+        });
+    }
+    Ok(signers.into_iter().map(|(_, id)| id).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_generation::generate_signed_commitment;
+    use crate::demo::generate_beefy_pairs_from_seed;
+    use crate::types::LeafData;
+    use crate::utils::{build_authority_merkle_root, generate_signer_proofs};
+    use beefy_primitives::crypto::Pair;
+    use sp_core::crypto::Pair as _;
+
+    // Commitment signed by 5 authorities, signature at `forged_position` is over other data
+    fn verify_with_forged_signature(
+        forged_position: usize,
+    ) -> Result<Vec<AuthorityId>, VerificationError> {
+        let pairs = generate_beefy_pairs_from_seed(5, 0);
+        let authority_ids = pairs.iter().map(|(_, id)| id.clone()).collect::<Vec<_>>();
+        let mut signed_commitment = generate_signed_commitment(
+            0,
+            1u64,
+            b"payload".to_vec(),
+            &pairs
+                .iter()
+                .map(|(pair, _)| pair.clone())
+                .collect::<Vec<Pair>>(),
+            &[],
+            SigningScheme::Raw,
+        );
+        signed_commitment.signatures[forged_position] =
+            Some(pairs[forged_position].0.sign(b"something else"));
+
+        verify_signed_commitment_with_proofs(
+            &signed_commitment,
+            build_authority_merkle_root(&authority_ids),
+            authority_ids.len() as u32,
+            &generate_signer_proofs(&authority_ids, &[0, 1, 2, 3, 4]),
+            SigningScheme::Raw,
+        )
+    }
+
+    #[test]
+    fn forged_signature_is_rejected_wherever_it_is() {
+        let first = verify_with_forged_signature(0);
+        assert_eq!(first, Err(VerificationError::SignatureInvalid));
+        assert_eq!(first, verify_with_forged_signature(4));
+    }
+
+    #[test]
+    fn signer_submitted_three_times_is_rejected() {
+        let pairs = generate_beefy_pairs_from_seed(5, 0);
+        let authority_ids = pairs.iter().map(|(_, id)| id.clone()).collect::<Vec<_>>();
+        let signed_commitment = generate_signed_commitment(
+            0,
+            1u64,
+            b"payload".to_vec(),
+            &pairs
+                .iter()
+                .map(|(pair, _)| pair.clone())
+                .collect::<Vec<Pair>>(),
+            &[],
+            SigningScheme::Raw,
+        );
+
+        assert_eq!(
+            verify_signed_commitment_with_proofs(
+                &signed_commitment,
+                build_authority_merkle_root(&authority_ids),
+                authority_ids.len() as u32,
+                &generate_signer_proofs(&authority_ids, &[2, 2, 2]),
+                SigningScheme::Raw,
+            ),
+            Err(VerificationError::DuplicateSigner { index: 2 })
+        );
+    }
+
+    // Commitment signed by first `signers` out of `set_len` authorities
+    fn verify_with_signers(
+        set_len: usize,
+        signers: usize,
+    ) -> Result<Vec<AuthorityId>, VerificationError> {
+        let pairs = generate_beefy_pairs_from_seed(set_len, 0);
+        let authority_ids = pairs.iter().map(|(_, id)| id.clone()).collect::<Vec<_>>();
+        let signed_commitment = generate_signed_commitment(
+            0,
+            1u64,
+            b"payload".to_vec(),
+            &pairs
+                .iter()
+                .map(|(pair, _)| pair.clone())
+                .collect::<Vec<Pair>>(),
+            &(signers..set_len).collect::<Vec<_>>(),
+            SigningScheme::Raw,
+        );
+
+        verify_signed_commitment_with_proofs(
+            &signed_commitment,
+            build_authority_merkle_root(&authority_ids),
+            set_len as u32,
+            &generate_signer_proofs(&authority_ids, &(0..signers as u32).collect::<Vec<_>>()),
+            SigningScheme::Raw,
+        )
+    }
+
+    #[test]
+    fn exactly_two_thirds_plus_one_signers_are_required() {
+        for &set_len in &[3, 4, 5, 10] {
+            let required = signature_threshold(set_len);
+            assert_eq!(required, set_len * 2 / 3 + 1);
+
+            assert_eq!(
+                verify_with_signers(set_len, required).unwrap().len(),
+                required
+            );
+            assert_eq!(
+                verify_with_signers(set_len, required - 1),
+                Err(VerificationError::NotEnoughSignatures {
+                    required,
+                    got: required - 1,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn beefy_payload_items_are_kept() {
+        let mmr_root = HashOutput::repeat_byte(7);
+        let encoded_payload = vec![
+            (*b"aa", b"before".to_vec()),
+            (MMR_ROOT_ID, mmr_root.encode()),
+            (*b"xx", b"after".to_vec()),
+        ]
+        .encode();
+
+        let payload =
+            CommitmentPayload::<LeafData>::from_beefy_payload(&encoded_payload, ValidatorSetId(3))
+                .unwrap();
+        assert_eq!(payload.mmr_root(), Some(MMRNode::Hash(mmr_root)));
+        assert_eq!(payload.get_extra(*b"aa"), Some(&b"before"[..]));
+        assert_eq!(payload.get_extra(*b"xx"), Some(&b"after"[..]));
+        assert_eq!(payload.new_validator_set_id, ValidatorSetId(3));
+        assert_eq!(payload.changed_authority_ids, None);
+    }
+
+    #[test]
+    fn beefy_payload_without_mmr_root_is_rejected() {
+        let encoded_payload = vec![(*b"xx", HashOutput::repeat_byte(7).encode())].encode();
+        assert!(CommitmentPayload::<LeafData>::from_beefy_payload(
+            &encoded_payload,
+            ValidatorSetId(0)
+        )
+        .is_err());
+
+        // Root item too short to hold a hash
+        let encoded_payload = vec![(MMR_ROOT_ID, vec![7u8; 31])].encode();
+        assert!(CommitmentPayload::<LeafData>::from_beefy_payload(
+            &encoded_payload,
+            ValidatorSetId(0)
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use crate::block_data::BlockData;
+use crate::block_generation::create_random_child_block_with_hasher;
+use crate::error::VerificationError;
+use crate::ethereum_actor::EthereumActor;
+use crate::fixtures::export_fixtures;
+use crate::proof_size::{proof_sizes, ProofSizeReport};
+use crate::types::{BlockNumber, HashOutput, HashingAlgo, LeafData, ValidatorSetId};
+use crate::utils::{generate_signer_proofs, HashKeyed};
+use beefy_primitives::crypto::{AuthorityId, Pair};
+use codec::Encode;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use sp_core::crypto::Pair as _;
+use sp_core::hashing::blake2_256;
+use sp_core::Hasher;
+use std::io;
+use std::path::Path;
+use std::string::ToString;
+use std::vec::Vec;
+
+fn generate_beefy_pairs(number: usize) -> Vec<(Pair, AuthorityId)> {
+    (0..number)
+        .map(|_| {
+            let pair = Pair::generate().0;
+            let public = pair.public();
+            (pair, public)
+        })
+        .collect()
+}
+
+// Same keys for the same seed, secret of each pair is derived from seed and its index
+pub fn generate_beefy_pairs_from_seed(number: usize, seed: u64) -> Vec<(Pair, AuthorityId)> {
+    (0..number as u64)
+        .map(|index| {
+            let pair = Pair::from_seed(&blake2_256(&(seed, index).encode()));
+            let public = pair.public();
+            (pair, public)
+        })
+        .collect()
+}
+
+// Outcome of a demo run, so that the run can be checked instead of just printed
+pub struct DemoReport<H: Hasher<Out = HashOutput> = HashingAlgo> {
+    pub blocks: Vec<BlockData<H>>,
+    // Relay block numbers at which ethereum actor switched to the next authority set
+    pub handovers: Vec<BlockNumber>,
+    pub final_authority_set_id: ValidatorSetId,
+    pub claim_verified: bool,
+    // Proof sizes of the claim on 5th block
+    pub proof_sizes: ProofSizeReport,
+}
+
+pub fn run_demo() -> Result<DemoReport, VerificationError> {
+    run_demo_with_hasher::<HashingAlgo>()
+}
+
+// Same as `run_demo` with beefy MMR, leaves, para heads trie and storage tries
+// all hashed with `H`
+pub fn run_demo_with_hasher<H: Hasher<Out = HashOutput>>(
+) -> Result<DemoReport<H>, VerificationError> {
+    let initial_authorities = generate_beefy_pairs(5);
+    let next_authorities = generate_beefy_pairs(6);
+    let mut rng = StdRng::from_entropy();
+    let mut create_block = |previous: Option<&BlockData<H>>, commit, new_authority_set| {
+        create_random_child_block_with_hasher::<H, HashKeyed<H>, LeafData<H>>(
+            previous,
+            commit,
+            new_authority_set,
+            &mut rng,
+        )
+    };
+
+    let mut blocks = vec![];
+    blocks.push(create_block(None, false, Some(initial_authorities.clone())));
+    for i in 0..10 {
+        // Authority set changes in 5th block, which needs a signed commitment
+        let block = if i == 3 {
+            create_block(
+                Some(blocks.last().unwrap()),
+                true,
+                Some(next_authorities.clone()),
+            )
+        } else {
+            create_block(Some(blocks.last().unwrap()), false, None)
+        };
+        blocks.push(block);
+    }
+    blocks.push(create_block(Some(blocks.last().unwrap()), true, None));
+
+    let initial_authority_ids: Vec<AuthorityId> = initial_authorities
+        .iter()
+        .map(|(_, id)| id.clone())
+        .collect();
+    let next_authority_ids: Vec<AuthorityId> =
+        next_authorities.iter().map(|(_, id)| id.clone()).collect();
+
+    // Ethereum actor is a smart contract maintaining authority sets
+    // It keeps last 10 finalized blocks around to verify claims against
+    let mut ethereum_actor = EthereumActor::<H>::from_genesis(&blocks[0], 10, None);
+    let mut handovers = vec![];
+
+    // We need to send 5th block to ethereum since the authority set changes in that block,
+    // then the last block which contains updated mmr root.
+    // Every authority signed, so relayer provides membership proof for all of them.
+    for (block, authority_ids) in [
+        (&blocks[4], &initial_authority_ids),
+        (blocks.last().unwrap(), &next_authority_ids),
+    ]
+    .iter()
+    {
+        let set_id = ethereum_actor.current_set_id();
+        ethereum_actor.ingest_new_header(
+            block.ethereum_view(),
+            generate_signer_proofs(
+                authority_ids,
+                &(0..authority_ids.len() as u32).collect::<Vec<u32>>(),
+            ),
+        )?;
+        if ethereum_actor.current_set_id() != set_id {
+            handovers.push(block.relay_header.number.into());
+        }
+    }
+
+    // We want to prove that 5th block is finalized, so that would mean we need to pass
+    // 4th index in blockdata vector element's header.
+    // It should be positioned at 4th index in merkle mountain range.
+    // Para heads merkle root of 5th block is part of its child (6th block)
+    // and MMR proof is generated against the last finalized block
+    let claim_proof = blocks[4].build_claim_proof(&blocks[5], blocks.last().unwrap());
+    let proof_sizes = proof_sizes(&blocks[4].ethereum_view(), &claim_proof.mmr_proof_items);
+
+    // If this call is successful this means that we have verified that a key value pair exists on substrate
+    // storage at specified block
+    let claim_verified = ethereum_actor
+        .verify_claim_proof(None, &claim_proof)
+        .is_ok();
+
+    Ok(DemoReport {
+        blocks,
+        handovers,
+        final_authority_set_id: ethereum_actor.current_set_id(),
+        claim_verified,
+        proof_sizes,
+    })
+}
+
+// Runs the demo and exports fixtures of its last block to `fixtures_path`
+pub fn beefy_light_client_demo(fixtures_path: &Path) -> io::Result<()> {
+    let report = run_demo().map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+
+    println!("Creating genesis block with Initial authority set id: 0");
+    for block in report.blocks.iter().skip(1) {
+        println!("Created block: {}", block.summary());
+    }
+    for number in report.handovers.iter() {
+        println!(
+            "Ethereum actor ingested block {} which contains updated authority id",
+            number
+        );
+    }
+    println!(
+        "Ethereum actor ingested last block (Which contains updated mmr root), \
+    authority set id is now {}",
+        report.final_authority_set_id
+    );
+
+    println!("Proof sizes for 5th block: {:?}", report.proof_sizes);
+    if report.claim_verified {
+        println!(
+            "We presented our beefy mmr proof, para header inclusion proof and storage proof which were accepted by ethereum actor"
+        );
+    } else {
+        println!("Ethereum actor rejected our claim on 5th block");
+    }
+
+    // Test vectors for the Ethereum side verifier
+    export_fixtures(fixtures_path, report.blocks.last().unwrap())?;
+    println!(
+        "Exported fixtures of the last block to {}",
+        fixtures_path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::{BeefyLeaf, Hashable};
+    use sp_core::KeccakHasher;
+
+    #[test]
+    fn demo_runs_with_keccak_everywhere() {
+        let report = run_demo_with_hasher::<KeccakHasher>().unwrap();
+        assert!(report.claim_verified);
+        assert_eq!(report.final_authority_set_id, ValidatorSetId(1));
+
+        // Leaves are hashed with Keccak too
+        let block = &report.blocks[4];
+        let root = block.para_header_merkle_root;
+        let keccak_leaf =
+            LeafData::<KeccakHasher>::from_relay_block(&block.relay_header, root).unwrap();
+        let blake_leaf =
+            LeafData::<HashingAlgo>::from_relay_block(&block.relay_header, root).unwrap();
+        assert_ne!(keccak_leaf.hash(), blake_leaf.hash());
+    }
+
+    #[test]
+    fn pairs_from_same_seed_are_identical() {
+        // Secret keys are compared, not only public ones
+        let pairs = |number, seed| {
+            generate_beefy_pairs_from_seed(number, seed)
+                .into_iter()
+                .map(|(pair, id)| {
+                    assert_eq!(pair.public(), id);
+                    (pair.to_raw_vec(), id)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(pairs(5, 0), pairs(5, 0));
+        assert_ne!(pairs(5, 0), pairs(5, 1));
+        // Pairs of a larger set start with the pairs of a smaller one
+        assert_eq!(pairs(6, 0)[..5], pairs(5, 0)[..]);
+
+        let mut secrets = pairs(5, 0)
+            .into_iter()
+            .map(|(secret, _)| secret)
+            .collect::<Vec<_>>();
+        secrets.sort();
+        secrets.dedup();
+        assert_eq!(secrets.len(), 5);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::types::{BlockNumber, ValidatorSetId};
+use alloc::string::String;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationError {
+    // Header sent for ingestion does not carry a signed commitment
+    MissingSignedCommitment,
+    // Bytes sent by relayer are not a valid SCALE encoding (of a view or signer multiproof)
+    DecodeFailed,
+    ValidatorSetIdMismatch {
+        expected: ValidatorSetId,
+        got: ValidatorSetId,
+    },
+    // Number of signature slots does not match the size of the authority set
+    SignatureCountMismatch {
+        expected: usize,
+        got: usize,
+    },
+    // Less than 2/3 + 1 of the authority set signed the commitment
+    NotEnoughSignatures {
+        required: usize,
+        got: usize,
+    },
+    SignatureInvalid,
+    // Signer is not part of the authority merkle root at the claimed index
+    AuthorityProofInvalid {
+        index: u32,
+    },
+    // Same signer index or authority id is provided more than once
+    DuplicateSigner {
+        index: u32,
+    },
+    // Signers are not proven by the multiproof against authority root
+    SignerMultiproofInvalid,
+    BlockNumberMismatch {
+        header: BlockNumber,
+        commitment: BlockNumber,
+    },
+    MmrRootMismatch,
+    // MMR root in relay header digest is missing or differs from the committed one
+    DigestRootMismatch,
+    // New validator set id must be current + 1 on handover and current otherwise
+    InvalidSetIdTransition {
+        current: ValidatorSetId,
+        new: ValidatorSetId,
+    },
+    // Block was already finalized with a different commitment
+    ConflictingCommitment {
+        block_number: BlockNumber,
+    },
+    // Reported commitments agree on the payload
+    NoEquivocation,
+    // View with the same MMR root is already ingested
+    AlreadyIngested,
+    // Commitment is older than configured staleness allows
+    StaleCommitment {
+        last_finalized: BlockNumber,
+        got: BlockNumber,
+    },
+    // Commitment is for a block before the last finalized block
+    CommitmentGoesBackwards {
+        last_finalized: BlockNumber,
+        got: BlockNumber,
+    },
+    // No block has been ingested by the actor yet
+    NoFinalizedBlock,
+    // Given MMR root is not part of the finalized history
+    UnknownMmrRoot,
+    // Commitment payload of the finalized block has no MMR root with given id
+    MissingPayloadRoot {
+        id: [u8; 2],
+    },
+    // Block with given number is not part of finalized history
+    NoSuchFinalizedBlock {
+        block_number: BlockNumber,
+    },
+    // Claimed block is not covered by the finalized MMR, which only has
+    // leaves of blocks before the finalized one
+    NotYetFinalized {
+        last_finalized: BlockNumber,
+        claimed: BlockNumber,
+    },
+    // MMR position is not a leaf of the finalized MMR
+    PositionOutOfRange {
+        position: u64,
+        mmr_size: u64,
+    },
+    // Finalized MMR has no leaves, so nothing can be proven against it
+    EmptyMmr,
+    // MMR a claim is verified against can not have more leaves than the finalized beefy MMR
+    MmrLeavesOutOfRange {
+        claimed: u64,
+        finalized: u64,
+    },
+    MmrProofFailed,
+    // Relay header does not announce authority set, so its MMR leaf cannot be built
+    MissingAuthoritySetDigest,
+    // Authorities do not match the set announced by the MMR leaf
+    AuthoritySetMismatch,
+    // MMR proof items could not be processed, carries the reason
+    MmrProofMalformed(String),
+    // Older MMR is not a prefix of the last finalized MMR
+    AncestryProofFailed,
+    ParaInclusionFailed,
+    // Para block does not have the number caller expects
+    UnexpectedParaBlock {
+        expected: BlockNumber,
+        got: BlockNumber,
+    },
+    StorageProofFailed,
+    // Claimed key or value is longer than the limit set on the actor
+    ClaimTooLarge {
+        key_len: usize,
+        value_len: usize,
+    },
+    // Relay views are not adjacent or do not match the given MMR proofs
+    InvalidRelayChain,
+    // Proof bundle refers to a trie node it does not contain
+    MalformedProofBundle,
+    // Key claimed to be absent exists in parachain storage
+    KeyExists,
+    // Keys within the range differ from the claimed ones
+    KeyRangeMismatch,
+    // Para header is not the direct child of the given parent
+    ParaHeaderLinkInvalid {
+        child: BlockNumber,
+        parent: BlockNumber,
+    },
+    // Relay header is not the direct child of the given parent
+    RelayHeaderLinkInvalid {
+        child: BlockNumber,
+        parent: BlockNumber,
+    },
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingSignedCommitment => {
+                write!(f, "Cannot ingest a block without signed commitment")
+            }
+            Self::DecodeFailed => write!(f, "Unable to decode relayer input"),
+            Self::ValidatorSetIdMismatch { expected, got } => write!(
+                f,
+                "Invalid validator set id, expected: {}, got: {}",
+                expected, got
+            ),
+            Self::SignatureCountMismatch { expected, got } => write!(
+                f,
+                "Number of signatures differ, expected: {}, got: {}",
+                expected, got
+            ),
+            Self::NotEnoughSignatures { required, got } => write!(
+                f,
+                "Not enough signatures, required: {}, got: {}",
+                required, got
+            ),
+            Self::SignatureInvalid => write!(f, "Invalid signature present"),
+            Self::AuthorityProofInvalid { index } => write!(
+                f,
+                "Unable to verify authority at index {} against authority root",
+                index
+            ),
+            Self::DuplicateSigner { index } => {
+                write!(f, "Signer at index {} is provided more than once", index)
+            }
+            Self::SignerMultiproofInvalid => {
+                write!(f, "Unable to verify signers against authority root")
+            }
+            Self::BlockNumberMismatch { header, commitment } => write!(
+                f,
+                "Invalid block number, header: {}, commitment: {}",
+                header, commitment
+            ),
+            Self::MmrRootMismatch => write!(f, "MMR root not matching to that of block"),
+            Self::DigestRootMismatch => write!(
+                f,
+                "MMR root in header digest not matching to that of commitment"
+            ),
+            Self::InvalidSetIdTransition { current, new } => write!(
+                f,
+                "Invalid validator set id transition from {} to {}",
+                current, new
+            ),
+            Self::ConflictingCommitment { block_number } => write!(
+                f,
+                "Block {} is already finalized with a different commitment",
+                block_number
+            ),
+            Self::AlreadyIngested => write!(f, "View with this MMR root is already ingested"),
+            Self::NoEquivocation => write!(f, "Commitments do not conflict"),
+            Self::StaleCommitment {
+                last_finalized,
+                got,
+            } => write!(
+                f,
+                "Commitment for block {} is too old, last finalized block: {}",
+                got, last_finalized
+            ),
+            Self::CommitmentGoesBackwards {
+                last_finalized,
+                got,
+            } => write!(
+                f,
+                "Commitment for block {} is older than last finalized block {}",
+                got, last_finalized
+            ),
+            Self::NoFinalizedBlock => write!(f, "Not ingested a block yet"),
+            Self::UnknownMmrRoot => write!(f, "MMR root is not part of finalized history"),
+            Self::MissingPayloadRoot { id } => write!(
+                f,
+                "Commitment payload has no MMR root with id: {}",
+                String::from_utf8_lossy(id)
+            ),
+            Self::NoSuchFinalizedBlock { block_number } => {
+                write!(f, "Block {} is not part of finalized history", block_number)
+            }
+            Self::NotYetFinalized {
+                last_finalized,
+                claimed,
+            } => write!(
+                f,
+                "Cannot verify claims for last finalized block ({}) or after that block ({})",
+                last_finalized, claimed
+            ),
+            Self::PositionOutOfRange { position, mmr_size } => write!(
+                f,
+                "Position {} is not a leaf of MMR with size {}",
+                position, mmr_size
+            ),
+            Self::EmptyMmr => write!(f, "Finalized MMR does not have any leaves"),
+            Self::MmrLeavesOutOfRange { claimed, finalized } => write!(
+                f,
+                "MMR with {} leaves is larger than finalized MMR with {} leaves",
+                claimed, finalized
+            ),
+            Self::MmrProofFailed => write!(f, "Block does not seems to be finalized"),
+            Self::MissingAuthoritySetDigest => {
+                write!(f, "Relay header does not announce its authority set")
+            }
+            Self::AuthoritySetMismatch => {
+                write!(
+                    f,
+                    "Authorities not matching to the set announced by MMR leaf"
+                )
+            }
+            Self::MmrProofMalformed(reason) => write!(f, "Malformed MMR proof: {}", reason),
+            Self::AncestryProofFailed => {
+                write!(f, "MMR is not an ancestor of last finalized MMR")
+            }
+            Self::UnexpectedParaBlock { expected, got } => write!(
+                f,
+                "Unexpected para block, expected: {}, got: {}",
+                expected, got
+            ),
+            Self::ParaInclusionFailed => {
+                write!(f, "Unable to verify inclusion of parachain block")
+            }
+            Self::StorageProofFailed => write!(f, "Unable to verify the storage claim"),
+            Self::ClaimTooLarge { key_len, value_len } => write!(
+                f,
+                "Storage claim too large, key length: {}, value length: {}",
+                key_len, value_len
+            ),
+            Self::InvalidRelayChain => write!(f, "Relay chain segment is not valid"),
+            Self::MalformedProofBundle => write!(f, "Proof bundle refers to missing trie node"),
+            Self::KeyExists => write!(f, "Key claimed to be absent exists in storage"),
+            Self::KeyRangeMismatch => {
+                write!(f, "Storage within the key range differs from the claim")
+            }
+            Self::ParaHeaderLinkInvalid { child, parent } => write!(
+                f,
+                "Para header {} is not a child of para header {}",
+                child, parent
+            ),
+            Self::RelayHeaderLinkInvalid { child, parent } => write!(
+                f,
+                "Relay header {} is not a child of relay header {}",
+                child, parent
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerificationError {}
@@ -1,149 +1,3761 @@
-use crate::block_generation::verify_signed_commitment;
+#[cfg(feature = "std")]
+use crate::block_data::BlockData;
+use crate::claim_proof::ClaimProof;
+use crate::commitment::{
+    verify_commitment_with_signer_multiproof, verify_signed_commitment_with_proofs,
+    CommitmentPayload, PayloadId, SigningScheme, MMR_ROOT_ID,
+};
+use crate::error::VerificationError;
 use crate::ethereum_view::EthereumView;
-use crate::mmr::{MMRNode, MergeStrategy};
-use crate::types::{HashOutput, HashingAlgo, LeafData, TestHeader, TrieLayout};
-use crate::utils::mmr_size_from_number_of_leaves;
+use crate::mmr::{verify_mmr_ancestry_proof_with_domain, AncestryProof, MMRNode, MergeStrategy};
+use crate::proof_bundle::DeduplicatedProofBundle;
+use crate::traits::{BeefyLeaf, Hashable, MmrDomain, NoDomain, ParaHeadKey};
+use crate::types::{
+    AuthoritySetDiff, BlockNumber, HashOutput, HashingAlgo, LeafData, MmrPosition, ParaId,
+    SignerProof, TestHeader, ValidatorSetId,
+};
+use crate::utils::{
+    authority_set_diff, build_authority_merkle_root, generate_signer_proofs, mmr_root_from_digest,
+    mmr_size_from_number_of_leaves, HashKeyed,
+};
+use crate::verification_cache::VerificationCache;
+use crate::verified_claim::VerifiedClaim;
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::string::ToString;
+use alloc::vec::Vec;
 use beefy_primitives::crypto::AuthorityId;
+use beefy_primitives::SignedCommitment;
 use codec::Encode;
+use core::cell::RefCell;
+use mmr_lib::helper::pos_height_in_tree;
 use mmr_lib::MerkleProof;
-use std::vec::Vec;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use sp_core::sp_std::marker::PhantomData;
+use sp_core::Hasher;
+use sp_trie::Layout;
 
-pub struct EthereumActor {
+// Claim limits of a new actor. Keys of real storage items are well below this,
+// values larger than a megabyte are only expected for the runtime code.
+pub const DEFAULT_MAX_KEY_LEN: usize = 1024;
+pub const DEFAULT_MAX_VALUE_LEN: usize = 1024 * 1024;
+
+// State transitions of the actor, same as events emitted by the contract
+#[derive(Clone, PartialEq, Debug)]
+pub enum ActorEvent<L: BeefyLeaf = LeafData> {
+    HeaderIngested {
+        block_number: BlockNumber,
+        mmr_root: MMRNode<L>,
+    },
+    AuthoritySetChanged {
+        old_set_id: ValidatorSetId,
+        new_set_id: ValidatorSetId,
+        diff: AuthoritySetDiff,
+    },
+}
+
+// Hasher `H` is used to verify beefy MMR, para heads trie and para storage trie proofs,
+// `K` is how para heads are keyed in para heads trie and `L` is the beefy MMR leaf.
+// Keys and leaves are hashed with `H` as well unless other ones are given.
+// Beefy MMR is hashed within domain `D`.
+pub struct EthereumActor<
+    H = HashingAlgo,
+    K = HashKeyed<H>,
+    L: BeefyLeaf = LeafData<H>,
+    D = NoDomain,
+> {
+    // Only merkle root of the authority set is stored,
+    // signers prove their membership during ingestion
+    current_authority_root: HashOutput,
+    current_authority_set_len: u32,
+    // Kept only for observability, signatures are verified against the root
     current_authorities: Vec<AuthorityId>,
-    current_set_id: u64,
-    last_finalized_block: Option<EthereumView>,
+    current_set_id: ValidatorSetId,
+    // Recently finalized blocks, oldest first. Claims can be verified
+    // against any of these, oldest one is evicted once `max_history` is reached
+    finalized_history: VecDeque<EthereumView<L>>,
+    max_history: usize,
+    // Hashes of MMR roots in `finalized_history`, so that the same view is not ingested twice
+    ingested_roots: BTreeSet<HashOutput>,
+    // Commitments older than last finalized block by more than this are rejected
+    max_staleness: Option<u64>,
+    // Authorities whose signatures verified for the last ingested commitment
+    last_signers: Option<Vec<AuthorityId>>,
+    // Events emitted since last `drain_events`, oldest first
+    events: Vec<ActorEvent<L>>,
+    // Successfully verified MMR proofs, set up with `with_cache`. Claims against
+    // same finalized root and relay block skip the MMR verification.
+    mmr_proof_cache: Option<RefCell<VerificationCache>>,
+    // Storage claims with longer keys or values are rejected before their proof is
+    // verified, `DEFAULT_MAX_KEY_LEN` and `DEFAULT_MAX_VALUE_LEN` unless set up with
+    // `with_claim_limits`
+    max_key_len: Option<usize>,
+    max_value_len: Option<usize>,
+    // What authorities sign, set up with `with_signing_scheme`
+    signing_scheme: SigningScheme,
+    hasher: PhantomData<H>,
+    para_head_key: PhantomData<K>,
+    domain: PhantomData<D>,
+}
+
+// Implemented by hand, derive would require hasher, para head key and domain to be `Clone`
+impl<H, K, L: BeefyLeaf, D> Clone for EthereumActor<H, K, L, D> {
+    fn clone(&self) -> Self {
+        Self {
+            current_authority_root: self.current_authority_root,
+            current_authority_set_len: self.current_authority_set_len,
+            current_authorities: self.current_authorities.clone(),
+            current_set_id: self.current_set_id,
+            finalized_history: self.finalized_history.clone(),
+            max_history: self.max_history,
+            ingested_roots: self.ingested_roots.clone(),
+            max_staleness: self.max_staleness,
+            last_signers: self.last_signers.clone(),
+            events: self.events.clone(),
+            mmr_proof_cache: self.mmr_proof_cache.clone(),
+            max_key_len: self.max_key_len,
+            max_value_len: self.max_value_len,
+            signing_scheme: self.signing_scheme,
+            hasher: PhantomData,
+            para_head_key: PhantomData,
+            domain: PhantomData,
+        }
+    }
 }
 
-impl EthereumActor {
-    pub fn new(initial_authorities: Vec<AuthorityId>, current_set_id: u64) -> Self {
+impl EthereumActor<HashingAlgo> {
+    pub fn new(
+        initial_authorities: Vec<AuthorityId>,
+        current_set_id: ValidatorSetId,
+        max_history: usize,
+        max_staleness: Option<u64>,
+    ) -> Self {
+        Self::with_hasher(
+            initial_authorities,
+            current_set_id,
+            max_history,
+            max_staleness,
+        )
+    }
+}
+
+impl<H: Hasher<Out = HashOutput>, K: ParaHeadKey, L: BeefyLeaf, D: MmrDomain>
+    EthereumActor<H, K, L, D>
+{
+    pub fn with_hasher(
+        initial_authorities: Vec<AuthorityId>,
+        current_set_id: ValidatorSetId,
+        max_history: usize,
+        max_staleness: Option<u64>,
+    ) -> Self {
         Self {
+            current_authority_root: build_authority_merkle_root(&initial_authorities),
+            current_authority_set_len: initial_authorities.len() as u32,
             current_authorities: initial_authorities,
             current_set_id,
-            last_finalized_block: None,
+            finalized_history: VecDeque::with_capacity(max_history),
+            max_history,
+            ingested_roots: BTreeSet::new(),
+            max_staleness,
+            last_signers: None,
+            events: vec![],
+            mmr_proof_cache: None,
+            max_key_len: Some(DEFAULT_MAX_KEY_LEN),
+            max_value_len: Some(DEFAULT_MAX_VALUE_LEN),
+            signing_scheme: SigningScheme::Raw,
+            hasher: PhantomData,
+            para_head_key: PhantomData,
+            domain: PhantomData,
+        }
+    }
+
+    // Starts already synced from a checkpoint, instead of trusting a hardcoded genesis.
+    // `trusted_authorities` (set `set_id`) are verified out-of-band and must have
+    // signed the checkpoint, which is then ingested as usual.
+    pub fn from_checkpoint(
+        checkpoint: EthereumView<L>,
+        trusted_authorities: Vec<AuthorityId>,
+        set_id: ValidatorSetId,
+        max_history: usize,
+        max_staleness: Option<u64>,
+    ) -> Result<Self, VerificationError> {
+        // Signature count is checked during ingestion, positions outside of the
+        // trusted set are left out here so that proofs can be generated
+        let signer_indices = checkpoint
+            .signed_commitment
+            .as_ref()
+            .ok_or(VerificationError::MissingSignedCommitment)?
+            .signatures
+            .iter()
+            .enumerate()
+            .filter(|(index, signature)| signature.is_some() && *index < trusted_authorities.len())
+            .map(|(index, _)| index as u32)
+            .collect::<Vec<u32>>();
+        let signer_proofs = generate_signer_proofs(&trusted_authorities, &signer_indices);
+
+        let mut actor = Self::with_hasher(trusted_authorities, set_id, max_history, max_staleness);
+        actor.ingest_new_header(checkpoint, signer_proofs)?;
+        Ok(actor)
+    }
+
+    // Remembers up to `capacity` verified MMR proofs
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.mmr_proof_cache = Some(RefCell::new(VerificationCache::new(capacity)));
+        self
+    }
+
+    // Limits length of claimed keys and values, `None` means no limit
+    pub fn with_claim_limits(
+        mut self,
+        max_key_len: Option<usize>,
+        max_value_len: Option<usize>,
+    ) -> Self {
+        self.max_key_len = max_key_len;
+        self.max_value_len = max_value_len;
+        self
+    }
+
+    // Commitments are expected to be signed using given scheme, `SigningScheme::Raw` by default
+    pub fn with_signing_scheme(mut self, signing_scheme: SigningScheme) -> Self {
+        self.signing_scheme = signing_scheme;
+        self
+    }
+
+    // Starts from the same authority set as the given genesis block
+    #[cfg(feature = "std")]
+    pub fn from_genesis<S>(
+        genesis: &BlockData<H, K, L, S>,
+        max_history: usize,
+        max_staleness: Option<u64>,
+    ) -> Self {
+        Self::with_hasher(
+            genesis
+                .current_authority_set
+                .iter()
+                .map(|(_, id)| id.clone())
+                .collect(),
+            ValidatorSetId(genesis.current_authority_set_id),
+            max_history,
+            max_staleness,
+        )
+    }
+
+    pub fn current_set_id(&self) -> ValidatorSetId {
+        self.current_set_id
+    }
+
+    pub fn current_authorities(&self) -> &[AuthorityId] {
+        &self.current_authorities
+    }
+
+    // None if no block has been ingested yet
+    pub fn last_signers(&self) -> Option<&[AuthorityId]> {
+        self.last_signers.as_deref()
+    }
+
+    pub fn drain_events(&mut self) -> Vec<ActorEvent<L>> {
+        core::mem::take(&mut self.events)
+    }
+
+    // None if no block has been ingested yet
+    pub fn last_finalized_number(&self) -> Option<BlockNumber> {
+        self.finalized_history
+            .back()
+            .map(|view| BlockNumber(view.relay_header.number))
+    }
+
+    // Finalized block with given MMR root or the latest one if no root is given
+    fn finalized_block(
+        &self,
+        at_mmr_root: Option<&MMRNode<L>>,
+    ) -> Result<&EthereumView<L>, VerificationError> {
+        match at_mmr_root {
+            None => self
+                .finalized_history
+                .back()
+                .ok_or(VerificationError::NoFinalizedBlock),
+            Some(mmr_root) => self
+                .finalized_history
+                .iter()
+                .rev()
+                .find(|view| &view.beefy_mmr_root == mmr_root)
+                .ok_or(VerificationError::UnknownMmrRoot),
+        }
+    }
+
+    // Every commitment, including the one enacting a handover, must be signed by the
+    // current (outgoing) set. New set is adopted only after this succeeds, so the
+    // incoming set can never authorize its own handover. Returns verified signers.
+    pub fn verify_handover_authorization(
+        &self,
+        signed_commitment: &SignedCommitment<u64, CommitmentPayload<L>>,
+        signer_proofs: &[SignerProof],
+    ) -> Result<Vec<AuthorityId>, VerificationError> {
+        stage_span!("signature_check");
+        self.check_validator_set_id(signed_commitment)?;
+        verify_signed_commitment_with_proofs(
+            signed_commitment,
+            self.current_authority_root,
+            self.current_authority_set_len,
+            signer_proofs,
+            self.signing_scheme,
+        )
+    }
+
+    fn check_validator_set_id(
+        &self,
+        signed_commitment: &SignedCommitment<u64, CommitmentPayload<L>>,
+    ) -> Result<(), VerificationError> {
+        let set_id = ValidatorSetId(signed_commitment.commitment.validator_set_id);
+        if set_id != self.current_set_id {
+            return Err(VerificationError::ValidatorSetIdMismatch {
+                expected: self.current_set_id,
+                got: set_id,
+            });
         }
+        Ok(())
     }
 
-    pub fn ingest_new_header(&mut self, ethereum_view: EthereumView) -> Result<(), String> {
+    // Same as `ingest_new_header`, but signers and their signatures come from a single
+    // SCALE encoded `SignerMultiproof` instead of one proof per signer. Signatures of
+    // the view's own signed commitment are not used.
+    pub fn ingest_new_header_with_multiproof(
+        &mut self,
+        ethereum_view: EthereumView<L>,
+        encoded_signer_multiproof: &[u8],
+    ) -> Result<(), VerificationError> {
+        let signed_commitment = ethereum_view
+            .signed_commitment
+            .as_ref()
+            .ok_or(VerificationError::MissingSignedCommitment)?;
+
+        let signers = {
+            stage_span!("signature_check");
+            self.check_validator_set_id(signed_commitment)?;
+            verify_commitment_with_signer_multiproof(
+                &signed_commitment.commitment,
+                self.current_authority_root,
+                self.current_authority_set_len,
+                encoded_signer_multiproof,
+                self.signing_scheme,
+            )?
+        };
+        self.check_verified_header(&ethereum_view)?;
+        self.apply_verified_header(ethereum_view, signers);
+        Ok(())
+    }
+
+    pub fn ingest_new_header(
+        &mut self,
+        ethereum_view: EthereumView<L>,
+        signer_proofs: Vec<SignerProof>,
+    ) -> Result<(), VerificationError> {
+        let signers = self.verify_new_header(&ethereum_view, &signer_proofs)?;
+        self.apply_verified_header(ethereum_view, signers);
+        Ok(())
+    }
+
+    // Runs every check of `ingest_new_header` without ingesting the view,
+    // e.g. for relayer to dry-run a submission before paying for it
+    pub fn check_commitment(
+        &self,
+        ethereum_view: &EthereumView<L>,
+        signer_proofs: &[SignerProof],
+    ) -> Result<(), VerificationError> {
+        self.verify_new_header(ethereum_view, signer_proofs)
+            .map(|_| ())
+    }
+
+    // Returns verified signers of the view's commitment
+    fn verify_new_header(
+        &self,
+        ethereum_view: &EthereumView<L>,
+        signer_proofs: &[SignerProof],
+    ) -> Result<Vec<AuthorityId>, VerificationError> {
         // Verify signed commitment
-        if ethereum_view.signed_commitment.is_none() {
-            return Err("Cannot ingest a block without signed commitment".to_string());
+        let signed_commitment = ethereum_view
+            .signed_commitment
+            .as_ref()
+            .ok_or(VerificationError::MissingSignedCommitment)?;
+
+        let signers = self.verify_handover_authorization(signed_commitment, signer_proofs)?;
+        self.check_verified_header(ethereum_view)?;
+        Ok(signers)
+    }
+
+    // Checks of the view besides its signatures, which must already be verified
+    fn check_verified_header(
+        &self,
+        ethereum_view: &EthereumView<L>,
+    ) -> Result<(), VerificationError> {
+        let signed_commitment = ethereum_view
+            .signed_commitment
+            .as_ref()
+            .ok_or(VerificationError::MissingSignedCommitment)?;
+
+        if ethereum_view.relay_header.number != signed_commitment.commitment.block_number {
+            return Err(VerificationError::BlockNumberMismatch {
+                header: ethereum_view.relay_header.number.into(),
+                commitment: signed_commitment.commitment.block_number.into(),
+            });
         }
 
-        let signed_commitment = ethereum_view.signed_commitment.as_ref().unwrap();
+        if signed_commitment.commitment.payload.mmr_root().as_ref()
+            != Some(&ethereum_view.beefy_mmr_root)
+        {
+            return Err(VerificationError::MmrRootMismatch);
+        }
 
-        if signed_commitment.commitment.validator_set_id != self.current_set_id {
-            return Err("Invalid validator set id".to_string());
+        // Header must commit to the same MMR root as the commitment
+        if mmr_root_from_digest::<L>(&ethereum_view.relay_header)
+            != Some(ethereum_view.beefy_mmr_root.clone())
+        {
+            return Err(VerificationError::DigestRootMismatch);
         }
 
-        let result = verify_signed_commitment(&signed_commitment, self.current_authorities.clone());
-        if result.is_err() {
-            return Err("Invalid signature".to_string());
+        // Staleness only depends on the block number, so that replayed old commitments
+        // are reported as such whether or not they are still in the history
+        if let (Some(last_finalized_number), Some(max_staleness)) =
+            (self.last_finalized_number(), self.max_staleness)
+        {
+            let block_number = BlockNumber(signed_commitment.commitment.block_number);
+            if block_number.0.saturating_add(max_staleness) < last_finalized_number.0 {
+                return Err(VerificationError::StaleCommitment {
+                    last_finalized: last_finalized_number,
+                    got: block_number,
+                });
+            }
         }
 
-        if ethereum_view.relay_header.number != signed_commitment.commitment.block_number {
-            return Err("Invalid block number".to_string());
+        if self
+            .ingested_roots
+            .contains(&ethereum_view.beefy_mmr_root.hash())
+        {
+            return Err(VerificationError::AlreadyIngested);
+        }
+
+        // Recently finalized blocks act as block number -> mmr root record,
+        // a different root for a block we already finalized means equivocation
+        if let Some(finalized_block) = self
+            .finalized_history
+            .iter()
+            .find(|view| view.relay_header.number == ethereum_view.relay_header.number)
+        {
+            if finalized_block.beefy_mmr_root != ethereum_view.beefy_mmr_root {
+                return Err(VerificationError::ConflictingCommitment {
+                    block_number: ethereum_view.relay_header.number.into(),
+                });
+            }
+        }
+
+        // Older commitments not caught above are not for any recently finalized block
+        if let Some(last_finalized_number) = self.last_finalized_number() {
+            let block_number = BlockNumber(signed_commitment.commitment.block_number);
+            if block_number < last_finalized_number {
+                return Err(VerificationError::CommitmentGoesBackwards {
+                    last_finalized: last_finalized_number,
+                    got: block_number,
+                });
+            }
         }
 
-        if ethereum_view.beefy_mmr_root != signed_commitment.commitment.payload.mmr_node {
-            return Err("MMR root not matching to that of block".to_string());
+        // Header directly following the last finalized one must extend it
+        if let Some(last_finalized_block) = self.finalized_history.back() {
+            if ethereum_view.relay_header.number == last_finalized_block.relay_header.number + 1 {
+                self.verify_relay_link(
+                    &ethereum_view.relay_header,
+                    &last_finalized_block.relay_header,
+                )?;
+            }
         }
 
-        if signed_commitment
+        // Authority set can only change by one handover at a time
+        let payload = &signed_commitment.commitment.payload;
+        let expected_set_id = if payload.changed_authority_ids.is_some() {
+            self.current_set_id.next()
+        } else {
+            self.current_set_id
+        };
+        if payload.new_validator_set_id != expected_set_id {
+            return Err(VerificationError::InvalidSetIdTransition {
+                current: self.current_set_id,
+                new: payload.new_validator_set_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    // Ingests the view, which must have passed `check_verified_header`, signed by `signers`
+    fn apply_verified_header(&mut self, ethereum_view: EthereumView<L>, signers: Vec<AuthorityId>) {
+        let payload = &ethereum_view
+            .signed_commitment
+            .as_ref()
+            .expect("Checked view has signed commitment")
             .commitment
-            .payload
-            .changed_authority_ids
-            .is_some()
-        {
-            self.current_authorities = signed_commitment
-                .commitment
-                .payload
-                .changed_authority_ids
-                .clone()
-                .unwrap();
-            self.current_set_id = signed_commitment.commitment.payload.new_validator_set_id;
+            .payload;
+
+        self.events.push(ActorEvent::HeaderIngested {
+            block_number: ethereum_view.relay_header.number.into(),
+            mmr_root: ethereum_view.beefy_mmr_root.clone(),
+        });
+
+        if let Some(changed_authority_ids) = payload.changed_authority_ids.as_ref() {
+            self.events.push(ActorEvent::AuthoritySetChanged {
+                old_set_id: self.current_set_id,
+                new_set_id: payload.new_validator_set_id,
+                diff: authority_set_diff(&self.current_authorities, changed_authority_ids),
+            });
+            self.current_authority_root = build_authority_merkle_root(changed_authority_ids);
+            self.current_authority_set_len = changed_authority_ids.len() as u32;
+            self.current_authorities = changed_authority_ids.clone();
+            self.current_set_id = payload.new_validator_set_id;
+        }
+
+        if self.finalized_history.len() >= self.max_history {
+            if let Some(evicted) = self.finalized_history.pop_front() {
+                self.ingested_roots.remove(&evicted.beefy_mmr_root.hash());
+            }
+        }
+        self.ingested_roots
+            .insert(ethereum_view.beefy_mmr_root.hash());
+        self.finalized_history.push_back(ethereum_view);
+        self.last_signers = Some(signers);
+    }
+
+    // Ingests commitment bearing views in order, applying every handover on the way,
+    // so that client offline across several handovers can catch up in one call.
+    // Set ids are checked to be contiguous before anything is ingested.
+    pub fn catch_up(
+        &mut self,
+        views: Vec<(EthereumView<L>, Vec<SignerProof>)>,
+    ) -> Result<(), VerificationError> {
+        let mut expected_set_id = self.current_set_id;
+        for (ethereum_view, _) in views.iter() {
+            let signed_commitment = ethereum_view
+                .signed_commitment
+                .as_ref()
+                .ok_or(VerificationError::MissingSignedCommitment)?;
+            let set_id = ValidatorSetId(signed_commitment.commitment.validator_set_id);
+            if set_id != expected_set_id {
+                return Err(VerificationError::ValidatorSetIdMismatch {
+                    expected: expected_set_id,
+                    got: set_id,
+                });
+            }
+            expected_set_id = signed_commitment.commitment.payload.new_validator_set_id;
+        }
+
+        for (ethereum_view, signer_proofs) in views {
+            self.ingest_new_header(ethereum_view, signer_proofs)?;
+        }
+        Ok(())
+    }
+
+    // Same as `catch_up` but signatures of all views are verified in parallel before
+    // views are applied in order. Authority set signing each view is known upfront
+    // from handovers announced by the views before it.
+    #[cfg(feature = "parallel")]
+    pub fn ingest_batch(
+        &mut self,
+        views: Vec<(EthereumView<L>, Vec<SignerProof>)>,
+    ) -> Result<(), VerificationError>
+    where
+        L: Send + Sync,
+    {
+        // (authority root, set length) signing each view
+        let mut authority_sets = Vec::with_capacity(views.len());
+        let mut set_id = self.current_set_id;
+        let mut authority_set = (self.current_authority_root, self.current_authority_set_len);
+        for (ethereum_view, _) in views.iter() {
+            let signed_commitment = ethereum_view
+                .signed_commitment
+                .as_ref()
+                .ok_or(VerificationError::MissingSignedCommitment)?;
+            let got = ValidatorSetId(signed_commitment.commitment.validator_set_id);
+            if got != set_id {
+                return Err(VerificationError::ValidatorSetIdMismatch {
+                    expected: set_id,
+                    got,
+                });
+            }
+            authority_sets.push(authority_set);
+
+            let payload = &signed_commitment.commitment.payload;
+            if let Some(changed_authority_ids) = payload.changed_authority_ids.as_ref() {
+                set_id = payload.new_validator_set_id;
+                authority_set = (
+                    build_authority_merkle_root(changed_authority_ids),
+                    changed_authority_ids.len() as u32,
+                );
+            }
         }
 
-        self.last_finalized_block = Some(ethereum_view);
+        // Actor itself is not `Sync`
+        let signing_scheme = self.signing_scheme;
+        let signers = views
+            .par_iter()
+            .zip(authority_sets.par_iter())
+            .map(
+                |((ethereum_view, signer_proofs), (authority_root, set_len))| {
+                    verify_signed_commitment_with_proofs(
+                        ethereum_view.signed_commitment.as_ref().unwrap(),
+                        *authority_root,
+                        *set_len,
+                        signer_proofs,
+                        signing_scheme,
+                    )
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
 
+        // Views are applied to a scratch copy, so that the actor is left unchanged
+        // if any view of the batch is rejected
+        let mut scratch = self.clone();
+        for ((ethereum_view, _), signers) in views.into_iter().zip(signers) {
+            scratch.check_verified_header(&ethereum_view)?;
+            scratch.apply_verified_header(ethereum_view, signers);
+        }
+        *self = scratch;
         Ok(())
     }
 
+    // Entry point for untrusted bytes received from relayer, malformed input is
+    // reported as error instead of panicking
+    pub fn ingest_encoded(
+        &mut self,
+        bytes: &[u8],
+        signer_proofs: Vec<SignerProof>,
+    ) -> Result<(), VerificationError> {
+        let ethereum_view =
+            EthereumView::<L>::from_bytes(bytes).map_err(|_| VerificationError::DecodeFailed)?;
+        self.ingest_new_header(ethereum_view, signer_proofs)
+    }
+
+    // Among competing views (e.g. during a reorg) picks the highest one whose commitment
+    // is validly signed by the current set, preferring the one enacting a handover at
+    // equal height. Returns its index, so that it can be ingested along with its proofs.
+    pub fn choose_best(&self, candidates: &[(EthereumView<L>, Vec<SignerProof>)]) -> Option<usize> {
+        candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (ethereum_view, signer_proofs))| {
+                let signed_commitment = ethereum_view.signed_commitment.as_ref()?;
+                let is_valid = signed_commitment.commitment.validator_set_id
+                    == self.current_set_id.0
+                    && verify_signed_commitment_with_proofs(
+                        signed_commitment,
+                        self.current_authority_root,
+                        self.current_authority_set_len,
+                        signer_proofs,
+                        self.signing_scheme,
+                    )
+                    .is_ok();
+                let enacts_handover = signed_commitment
+                    .commitment
+                    .payload
+                    .changed_authority_ids
+                    .is_some();
+                if is_valid {
+                    Some((index, (ethereum_view.relay_header.number, enacts_handover)))
+                } else {
+                    None
+                }
+            })
+            .max_by_key(|(_, rank)| *rank)
+            .map(|(index, _)| index)
+    }
+
+    // Verifies that both views carry commitments for the same block, validly signed by
+    // the current set but with different payloads. Returns authorities who signed both.
+    pub fn report_equivocation(
+        &self,
+        view_a: EthereumView<L>,
+        signer_proofs_a: Vec<SignerProof>,
+        view_b: EthereumView<L>,
+        signer_proofs_b: Vec<SignerProof>,
+    ) -> Result<Vec<AuthorityId>, VerificationError> {
+        let signed_commitment_a = view_a
+            .signed_commitment
+            .as_ref()
+            .ok_or(VerificationError::MissingSignedCommitment)?;
+        let signed_commitment_b = view_b
+            .signed_commitment
+            .as_ref()
+            .ok_or(VerificationError::MissingSignedCommitment)?;
+
+        if signed_commitment_a.commitment.block_number
+            != signed_commitment_b.commitment.block_number
+        {
+            return Err(VerificationError::BlockNumberMismatch {
+                header: signed_commitment_a.commitment.block_number.into(),
+                commitment: signed_commitment_b.commitment.block_number.into(),
+            });
+        }
+
+        if signed_commitment_a.commitment.payload.encode()
+            == signed_commitment_b.commitment.payload.encode()
+        {
+            return Err(VerificationError::NoEquivocation);
+        }
+
+        for signed_commitment in [signed_commitment_a, signed_commitment_b].iter() {
+            self.check_validator_set_id(signed_commitment)?;
+        }
+
+        verify_signed_commitment_with_proofs(
+            signed_commitment_a,
+            self.current_authority_root,
+            self.current_authority_set_len,
+            &signer_proofs_a,
+            self.signing_scheme,
+        )?;
+        verify_signed_commitment_with_proofs(
+            signed_commitment_b,
+            self.current_authority_root,
+            self.current_authority_set_len,
+            &signer_proofs_b,
+            self.signing_scheme,
+        )?;
+
+        Ok(signer_proofs_a
+            .into_iter()
+            .filter(|(index_a, _, _)| {
+                signer_proofs_b
+                    .iter()
+                    .any(|(index_b, _, _)| index_a == index_b)
+            })
+            .map(|(_, authority_id, _)| authority_id)
+            .collect())
+    }
+
+    // Relay block is proven against the MMR with `root_id` committed by the finalized
+    // block, `MMR_ROOT_ID` being its beefy MMR. Chains running several MMRs commit
+    // to the other ones (and their leaf counts) in the commitment payload.
     pub fn verify_claim(
         &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        root_id: PayloadId,
         at_relay_block: TestHeader,
-        beefy_mmr_proof_items: Vec<MMRNode<LeafData>>,
-        block_pos_in_mmr: u64,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
         para_block: TestHeader,
+        // Para block is rejected unless it has this number, if given
+        expected_para_number: Option<BlockNumber>,
         para_block_inclusion_proof: Vec<Vec<u8>>,
         para_block_merkle_root: HashOutput,
         claimed_kv: (Vec<u8>, Vec<u8>),
         kv_proof: Vec<Vec<u8>>,
-    ) -> Result<(), String> {
-        if self.last_finalized_block.is_none() {
-            return Err("Not ingested a block yet".to_string());
+    ) -> Result<(), VerificationError> {
+        if root_id == MMR_ROOT_ID {
+            return self
+                .verify_claim_extract(
+                    at_mmr_root,
+                    at_relay_block,
+                    beefy_mmr_proof_items,
+                    block_pos_in_mmr,
+                    para_id,
+                    para_block,
+                    expected_para_number,
+                    para_block_inclusion_proof,
+                    para_block_merkle_root,
+                    claimed_kv,
+                    kv_proof,
+                )
+                .map(|_| ());
         }
-        let last_finalized_block = self.last_finalized_block.as_ref().unwrap();
 
-        if last_finalized_block.relay_header.number <= at_relay_block.number {
-            return Err(
-                "Cannot verify claims for last finalized block or after that block".to_string(),
-            );
+        self.check_claim_inputs(&para_block, expected_para_number, &claimed_kv)?;
+        let finalized_block = self.finalized_block(at_mmr_root.as_ref())?;
+        let (mmr_root, mmr_leaves) = payload_mmr(finalized_block, root_id)?;
+
+        let leaf = L::from_relay_block(&at_relay_block, para_block_merkle_root)
+            .ok_or(VerificationError::MissingAuthoritySetDigest)?;
+        {
+            stage_span!("mmr_verification");
+            self.verify_leaves_in_mmr(
+                &finalized_block.relay_header,
+                mmr_root,
+                mmr_leaves,
+                beefy_mmr_proof_items,
+                vec![(block_pos_in_mmr.into(), MMRNode::Data(leaf))],
+            )?;
         }
 
-        let mmr_root = last_finalized_block.beefy_mmr_root.clone();
-        let mmr_size = mmr_size_from_number_of_leaves(last_finalized_block.beefy_mmr_leaves);
+        verify_para_head_proof::<H, K>(
+            para_id,
+            &para_block,
+            para_block_merkle_root,
+            para_block_inclusion_proof,
+        )?;
+        verify_storage_proof::<H>(para_block.state_root, claimed_kv, kv_proof)
+    }
+
+    // Same as `verify_claim` but returns verified para block data, so that
+    // callers can act on it without decoding the proofs again
+    pub fn verify_claim_extract(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: TestHeader,
+        expected_para_number: Option<BlockNumber>,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        para_block_merkle_root: HashOutput,
+        claimed_kv: (Vec<u8>, Vec<u8>),
+        kv_proof: Vec<Vec<u8>>,
+    ) -> Result<VerifiedClaim, VerificationError> {
+        self.verify_claim_extract_with_para_hasher::<H>(
+            at_mmr_root,
+            at_relay_block,
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_id,
+            para_block,
+            expected_para_number,
+            para_block_inclusion_proof,
+            para_block_merkle_root,
+            claimed_kv,
+            kv_proof,
+        )
+    }
+
+    // Same as `verify_claim` but returns hash of the proven MMR leaf, e.g. to be emitted
+    // in an event, so that callers do not have to rebuild the leaf themselves
+    pub fn verify_claim_with_leaf_hash(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: TestHeader,
+        expected_para_number: Option<BlockNumber>,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        para_block_merkle_root: HashOutput,
+        claimed_kv: (Vec<u8>, Vec<u8>),
+        kv_proof: Vec<Vec<u8>>,
+    ) -> Result<HashOutput, VerificationError> {
+        // Same leaf as the one verified against the MMR
+        let leaf = L::from_relay_block(&at_relay_block, para_block_merkle_root)
+            .ok_or(VerificationError::MissingAuthoritySetDigest)?;
+
+        self.verify_claim(
+            at_mmr_root,
+            MMR_ROOT_ID,
+            at_relay_block,
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_id,
+            para_block,
+            expected_para_number,
+            para_block_inclusion_proof,
+            para_block_merkle_root,
+            claimed_kv,
+            kv_proof,
+        )?;
+
+        Ok(leaf.hash())
+    }
 
-        println!("MMR root: {:?}, size: {}", mmr_root, mmr_size);
+    // Same as `verify_claim_extract` for a parachain whose storage trie uses hasher `PH`
+    // (e.g. Blake2) while para heads trie uses the relay hasher `H`
+    pub fn verify_claim_extract_with_para_hasher<PH: Hasher<Out = HashOutput>>(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: TestHeader,
+        expected_para_number: Option<BlockNumber>,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        para_block_merkle_root: HashOutput,
+        claimed_kv: (Vec<u8>, Vec<u8>),
+        kv_proof: Vec<Vec<u8>>,
+    ) -> Result<VerifiedClaim, VerificationError> {
+        self.check_claim_inputs(&para_block, expected_para_number, &claimed_kv)?;
 
-        let merkle_proof = MerkleProof::<_, MergeStrategy<LeafData, HashingAlgo>>::new(
-            mmr_size,
+        self.verify_para_block_finalized(
+            at_mmr_root,
+            at_relay_block,
             beefy_mmr_proof_items,
-        );
-        if !merkle_proof
-            .verify(
-                mmr_root,
-                vec![(
-                    block_pos_in_mmr,
-                    MMRNode::Data((
-                        at_relay_block.number,
-                        at_relay_block.hash(),
-                        para_block_merkle_root,
-                    )),
-                )],
-            )
-            .unwrap()
+            block_pos_in_mmr,
+            para_id,
+            &para_block,
+            para_block_inclusion_proof,
+            para_block_merkle_root,
+        )?;
+
+        // We now trust the para block
+        verify_storage_proof::<PH>(para_block.state_root, claimed_kv.clone(), kv_proof)?;
+
+        Ok(VerifiedClaim {
+            para_id,
+            para_block_number: para_block.number.into(),
+            para_state_root: para_block.state_root,
+            claimed_kv,
+        })
+    }
+
+    // Proves that para block is finalized, running MMR and para inclusion checks only.
+    // Storage claim (kv pair, proof) is verified against the para block too, if given.
+    pub fn verify_finality_only(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: TestHeader,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        para_block_merkle_root: HashOutput,
+        storage_claim: Option<((Vec<u8>, Vec<u8>), Vec<Vec<u8>>)>,
+    ) -> Result<(), VerificationError> {
+        if let Some((claimed_kv, _)) = &storage_claim {
+            self.check_claim_size(claimed_kv)?;
+        }
+        self.verify_para_block_finalized(
+            at_mmr_root,
+            at_relay_block,
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_id,
+            &para_block,
+            para_block_inclusion_proof,
+            para_block_merkle_root,
+        )?;
+
+        match storage_claim {
+            Some((claimed_kv, kv_proof)) => {
+                verify_storage_proof::<H>(para_block.state_root, claimed_kv, kv_proof)
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Same as `verify_claim` but para heads root of the target relay block is taken from
+    // its child instead of being passed in. `relay_chain` holds adjacent relay views from
+    // the newest one down to the target, `mmr_proofs[i]` is (position, proof items) of
+    // `relay_chain[i + 1]`. Parent of the newest view is proven against the finalized
+    // MMR and every older one against the MMR root committed in the header digest of
+    // the view proven before it, so proof size grows linearly with the number of hops.
+    pub fn verify_claim_via_chain(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        relay_chain: Vec<EthereumView<L>>,
+        mmr_proofs: Vec<(MmrPosition, Vec<MMRNode<L>>)>,
+        para_id: ParaId,
+        para_block: TestHeader,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        claimed_kv: (Vec<u8>, Vec<u8>),
+        kv_proof: Vec<Vec<u8>>,
+    ) -> Result<(), VerificationError> {
+        if relay_chain.len() < 2 || mmr_proofs.len() != relay_chain.len() - 1 {
+            return Err(VerificationError::InvalidRelayChain);
+        }
+        self.check_claim_size(&claimed_kv)?;
+        let finalized_block = self.finalized_block(at_mmr_root.as_ref())?;
+
+        // Last proven parent, whose header commits to the MMR the next hop is proven against
+        let mut proven_parent: Option<&TestHeader> = None;
+        for ((child, parent), (block_pos_in_mmr, beefy_mmr_proof_items)) in relay_chain
+            .iter()
+            .zip(relay_chain.iter().skip(1))
+            .zip(mmr_proofs.into_iter())
         {
-            return Err("Block does not seems to be finalized".to_string());
+            if child.relay_header.parent_hash != parent.relay_header.hash()
+                || child.relay_header.number != parent.relay_header.number + 1
+            {
+                return Err(VerificationError::InvalidRelayChain);
+            }
+
+            let (mmr_block, mmr_root, mmr_leaves) = match proven_parent {
+                None => (
+                    &finalized_block.relay_header,
+                    finalized_block.beefy_mmr_root.clone(),
+                    finalized_block.beefy_mmr_leaves,
+                ),
+                // MMR gains a leaf per block, so the older one lacks a leaf per block since
+                Some(header) => (
+                    header,
+                    mmr_root_from_digest::<L>(header)
+                        .ok_or(VerificationError::DigestRootMismatch)?,
+                    finalized_block
+                        .beefy_mmr_leaves
+                        .saturating_sub(finalized_block.relay_header.number - header.number),
+                ),
+            };
+            let leaf = L::from_relay_block(&parent.relay_header, child.para_header_merkle_root)
+                .ok_or(VerificationError::MissingAuthoritySetDigest)?;
+            {
+                stage_span!("mmr_verification");
+                self.verify_leaves_in_mmr(
+                    mmr_block,
+                    mmr_root,
+                    mmr_leaves,
+                    beefy_mmr_proof_items,
+                    vec![(block_pos_in_mmr.into(), MMRNode::Data(leaf))],
+                )?;
+            }
+            // Parent is now trusted and so is the MMR root in its digest
+            proven_parent = Some(&parent.relay_header);
         }
 
-        // We now trust the para block merkle root
-        // So, let's check if given para block is indeed part of that merkle root
-        // if yes, that would mean that para block is finalized
-        // and by extension the storage claim is also finalized.
-        let items = vec![(para_block.hash(), Some(para_block.encode()))];
-        if sp_trie::verify_trie_proof::<TrieLayout, _, _, _>(
-            &para_block_merkle_root,
-            &*para_block_inclusion_proof,
-            items.iter(),
+        // Para heads root of the target is the one its leaf was proven with
+        let child_of_target = &relay_chain[relay_chain.len() - 2];
+        verify_para_head_proof::<H, K>(
+            para_id,
+            &para_block,
+            child_of_target.para_header_merkle_root,
+            para_block_inclusion_proof,
+        )?;
+        verify_storage_proof::<H>(para_block.state_root, claimed_kv, kv_proof)
+    }
+
+    // Same as `verify_claim` but para inclusion and storage proofs are
+    // reconstituted from the shared nodes of the bundle
+    pub fn verify_claim_with_bundle(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: TestHeader,
+        para_block_merkle_root: HashOutput,
+        claimed_kv: (Vec<u8>, Vec<u8>),
+        proof_bundle: DeduplicatedProofBundle,
+    ) -> Result<(), VerificationError> {
+        let para_block_inclusion_proof = proof_bundle
+            .para_inclusion_proof()
+            .ok_or(VerificationError::MalformedProofBundle)?;
+        let kv_proof = proof_bundle
+            .kv_proof()
+            .ok_or(VerificationError::MalformedProofBundle)?;
+
+        self.verify_claim(
+            at_mmr_root,
+            MMR_ROOT_ID,
+            at_relay_block,
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_id,
+            para_block,
+            None,
+            para_block_inclusion_proof,
+            para_block_merkle_root,
+            claimed_kv,
+            kv_proof,
         )
-        .is_err()
-        {
-            return Err("Unable to verify inclusion of parachain block".to_string());
+    }
+
+    // Same as `verify_claim` with all proofs taken from `claim_proof`
+    pub fn verify_claim_proof(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        claim_proof: &ClaimProof<L>,
+    ) -> Result<(), VerificationError> {
+        let claim_proof = claim_proof.clone();
+        self.verify_claim(
+            at_mmr_root,
+            MMR_ROOT_ID,
+            claim_proof.relay_header,
+            claim_proof.mmr_proof_items,
+            claim_proof.block_pos_in_mmr,
+            claim_proof.para_id,
+            claim_proof.para_header,
+            None,
+            claim_proof.para_header_inclusion_proof,
+            claim_proof.para_header_merkle_root,
+            claim_proof.claimed_kv,
+            claim_proof.kv_proof,
+        )
+    }
+
+    // Same as `verify_claim` but against MMR root of the finalized block with given number
+    pub fn verify_claim_at(
+        &self,
+        finalized_block_number: BlockNumber,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: TestHeader,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        para_block_merkle_root: HashOutput,
+        claimed_kv: (Vec<u8>, Vec<u8>),
+        kv_proof: Vec<Vec<u8>>,
+    ) -> Result<(), VerificationError> {
+        let finalized_block = self
+            .finalized_history
+            .iter()
+            .find(|view| BlockNumber(view.relay_header.number) == finalized_block_number)
+            .ok_or(VerificationError::NoSuchFinalizedBlock {
+                block_number: finalized_block_number,
+            })?;
+
+        self.verify_claim(
+            Some(finalized_block.beefy_mmr_root.clone()),
+            MMR_ROOT_ID,
+            at_relay_block,
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_id,
+            para_block,
+            None,
+            para_block_inclusion_proof,
+            para_block_merkle_root,
+            claimed_kv,
+            kv_proof,
+        )
+    }
+
+    // Same as `verify_claim` but all claims are proven by a single storage multiproof
+    pub fn verify_multi_claim(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: TestHeader,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        para_block_merkle_root: HashOutput,
+        claimed_kvs: Vec<(Vec<u8>, Vec<u8>)>,
+        kv_multiproof: Vec<Vec<u8>>,
+    ) -> Result<(), VerificationError> {
+        for claimed_kv in claimed_kvs.iter() {
+            self.check_claim_size(claimed_kv)?;
+        }
+        self.verify_para_block_finalized(
+            at_mmr_root,
+            at_relay_block,
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_id,
+            &para_block,
+            para_block_inclusion_proof,
+            para_block_merkle_root,
+        )?;
+
+        // We now trust the para block
+        verify_storage_multiproof::<H>(para_block.state_root, claimed_kvs, kv_multiproof)
+    }
+
+    // Same as `verify_multi_claim` but proves that `claimed_kvs` are all the pairs with
+    // keys in `[lo, hi]`. Range proof is the set of raw trie nodes visited while iterating
+    // the range (as recorded by a `Recorder`), iterating it fails if any node is missing.
+    pub fn verify_key_range_claim(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: TestHeader,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        para_block_merkle_root: HashOutput,
+        lo: Vec<u8>,
+        hi: Vec<u8>,
+        claimed_kvs: Vec<(Vec<u8>, Vec<u8>)>,
+        range_proof: Vec<Vec<u8>>,
+    ) -> Result<(), VerificationError> {
+        for claimed_kv in claimed_kvs.iter() {
+            self.check_claim_size(claimed_kv)?;
         }
+        self.verify_para_block_finalized(
+            at_mmr_root,
+            at_relay_block,
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_id,
+            &para_block,
+            para_block_inclusion_proof,
+            para_block_merkle_root,
+        )?;
+
+        // We now trust the para block
+        verify_key_range_proof::<H>(para_block.state_root, &lo, &hi, claimed_kvs, range_proof)
+    }
+
+    // Only verifies the storage claim, for clients tracking para state roots out-of-band
+    pub fn verify_storage_against_root(
+        &self,
+        state_root: HashOutput,
+        claimed_kv: (Vec<u8>, Vec<u8>),
+        kv_proof: Vec<Vec<u8>>,
+    ) -> Result<(), VerificationError> {
+        self.check_claim_size(&claimed_kv)?;
+        verify_storage_proof::<H>(state_root, claimed_kv, kv_proof)
+    }
+
+    // Same as `verify_claim` but proves that `key` does not exist in para block's storage
+    pub fn verify_absence_claim(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: TestHeader,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        para_block_merkle_root: HashOutput,
+        key: Vec<u8>,
+        absence_proof: Vec<Vec<u8>>,
+    ) -> Result<(), VerificationError> {
+        // Absent key has no value, so only length of the key is limited
+        let claimed_kv = (key, Vec::new());
+        self.check_claim_size(&claimed_kv)?;
+        self.verify_para_block_finalized(
+            at_mmr_root,
+            at_relay_block,
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_id,
+            &para_block,
+            para_block_inclusion_proof,
+            para_block_merkle_root,
+        )?;
 
         // We now trust the para block
-        let storage_root = para_block.state_root;
-        let items = vec![(claimed_kv.0, Some(claimed_kv.1))];
-        if sp_trie::verify_trie_proof::<TrieLayout, _, _, _>(
-            &storage_root,
-            &*kv_proof,
+        stage_span!("storage_proof");
+        let items = vec![(claimed_kv.0, None::<Vec<u8>>)];
+        match sp_trie::verify_trie_proof::<Layout<H>, _, _, _>(
+            &para_block.state_root,
+            &*absence_proof,
             items.iter(),
-        )
-        .is_err()
-        {
-            return Err("Unable to verify the storage claim".to_string());
+        ) {
+            Ok(()) => Ok(()),
+            // Proof shows a value for the key
+            Err(sp_trie::VerifyError::ValueMismatch(_)) => Err(VerificationError::KeyExists),
+            Err(_) => Err(VerificationError::StorageProofFailed),
         }
+    }
 
-        Ok(())
+    // Verifies finality of para block once and then checks every storage claim against
+    // its state root. Outer error means para block itself could not be verified.
+    pub fn verify_claims_batch(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: TestHeader,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        para_block_merkle_root: HashOutput,
+        claims: Vec<((Vec<u8>, Vec<u8>), Vec<Vec<u8>>)>,
+    ) -> Result<Vec<Result<(), VerificationError>>, VerificationError> {
+        self.verify_para_block_finalized(
+            at_mmr_root,
+            at_relay_block,
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_id,
+            &para_block,
+            para_block_inclusion_proof,
+            para_block_merkle_root,
+        )?;
+
+        Ok(claims
+            .into_iter()
+            .map(|(claimed_kv, kv_proof)| {
+                self.check_claim_size(&claimed_kv)?;
+                verify_storage_proof::<H>(para_block.state_root, claimed_kv, kv_proof)
+            })
+            .collect())
+    }
+
+    // Checks that `child` directly extends `parent`. Together with inclusion proofs
+    // of both headers this proves a contiguous para segment is finalized.
+    pub fn verify_para_header_link(
+        &self,
+        child: TestHeader,
+        parent: TestHeader,
+    ) -> Result<(), VerificationError> {
+        if child.parent_hash != parent.hash() || child.number != parent.number + 1 {
+            return Err(VerificationError::ParaHeaderLinkInvalid {
+                child: child.number.into(),
+                parent: parent.number.into(),
+            });
+        }
+        Ok(())
+    }
+
+    // Same as `verify_para_header_link` for relay headers
+    pub fn verify_relay_link(
+        &self,
+        child: &TestHeader,
+        parent: &TestHeader,
+    ) -> Result<(), VerificationError> {
+        if child.parent_hash != parent.hash() || child.number != parent.number + 1 {
+            return Err(VerificationError::RelayHeaderLinkInvalid {
+                child: child.number.into(),
+                parent: parent.number.into(),
+            });
+        }
+        Ok(())
+    }
+
+    // Verifies that all given leaves are part of the finalized MMR root (latest one by default)
+    // using a single (combined) MMR proof
+    pub fn verify_finalized_leaves(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        leaves: Vec<(MmrPosition, MMRNode<L>)>,
+    ) -> Result<(), VerificationError> {
+        stage_span!("mmr_verification");
+        let finalized_block = self.finalized_block(at_mmr_root.as_ref())?;
+        self.verify_leaves_in_mmr(
+            &finalized_block.relay_header,
+            finalized_block.beefy_mmr_root.clone(),
+            finalized_block.beefy_mmr_leaves,
+            beefy_mmr_proof_items,
+            leaves
+                .into_iter()
+                .map(|(pos, leaf)| (pos.into(), leaf))
+                .collect(),
+        )
+    }
+
+    // Verifies leaves against MMR with `mmr_root` and `mmr_leaves` leaves, which is
+    // committed by trusted relay header `mmr_block`
+    fn verify_leaves_in_mmr(
+        &self,
+        mmr_block: &TestHeader,
+        mmr_root: MMRNode<L>,
+        mmr_leaves: u64,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        leaves: Vec<(u64, MMRNode<L>)>,
+    ) -> Result<(), VerificationError> {
+        if mmr_leaves == 0 {
+            return Err(VerificationError::EmptyMmr);
+        }
+
+        for (_, leaf) in leaves.iter() {
+            let claimed_block_number = match leaf {
+                MMRNode::Data(leaf) => leaf.relay_block_number(),
+                MMRNode::Hash(_) => return Err(VerificationError::MmrProofFailed),
+            };
+            // Leaf of block N is appended while building block N + 1, so MMR root of
+            // a block covers its ancestors only, the parent being the latest leaf
+            let last_finalized = BlockNumber(mmr_block.number);
+            if last_finalized <= claimed_block_number {
+                return Err(VerificationError::NotYetFinalized {
+                    last_finalized,
+                    claimed: claimed_block_number,
+                });
+            }
+        }
+
+        let mmr_size = mmr_size_from_number_of_leaves(mmr_leaves);
+
+        // Positions must point to leaves (not internal nodes) within finalized MMR
+        for (pos, _) in leaves.iter() {
+            if *pos >= mmr_size || pos_height_in_tree(*pos) != 0 {
+                return Err(VerificationError::PositionOutOfRange {
+                    position: *pos,
+                    mmr_size,
+                });
+            }
+        }
+
+        #[cfg(feature = "trace")]
+        tracing::debug!(mmr_root = %mmr_root.pretty(), mmr_size);
+
+        let merkle_proof =
+            MerkleProof::<_, MergeStrategy<L, H, D>>::new(mmr_size, beefy_mmr_proof_items);
+        // Malformed proof from untrusted relayer must not panic
+        let is_valid = merkle_proof
+            .verify(mmr_root, leaves)
+            .map_err(|e| VerificationError::MmrProofMalformed(e.to_string()))?;
+        if !is_valid {
+            return Err(VerificationError::MmrProofFailed);
+        }
+
+        Ok(())
+    }
+
+    // Verifies that para heads merkle root of given relay block is finalized,
+    // without checking any para block against it
+    pub fn verify_finalized_para_root(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        claimed_para_root: HashOutput,
+    ) -> Result<(), VerificationError> {
+        let leaf = MMRNode::Data(
+            L::from_relay_block(&at_relay_block, claimed_para_root)
+                .ok_or(VerificationError::MissingAuthoritySetDigest)?,
+        );
+
+        let finalized_block = self.finalized_block(at_mmr_root.as_ref())?;
+        let cache_key = (
+            finalized_block.beefy_mmr_root.hash(),
+            block_pos_in_mmr.into(),
+            leaf.hash(),
+        );
+        if let Some(cache) = &self.mmr_proof_cache {
+            if cache.borrow_mut().contains(&cache_key) {
+                return Ok(());
+            }
+        }
+
+        self.verify_finalized_leaves(
+            at_mmr_root,
+            beefy_mmr_proof_items,
+            vec![(block_pos_in_mmr, leaf)],
+        )?;
+
+        if let Some(cache) = &self.mmr_proof_cache {
+            cache.borrow_mut().insert(cache_key);
+        }
+        Ok(())
+    }
+
+    // Verifies that relay block of an earlier commitment is a leaf of a later finalized
+    // MMR (latest one by default), i.e. that the chain did extend the block it signed
+    // a commitment for. Leaf of the block is built from `para_heads_root` of that block.
+    pub fn verify_commitment_block_in_mmr(
+        &self,
+        committed_view: &EthereumView<L>,
+        at_mmr_root: Option<MMRNode<L>>,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_heads_root: HashOutput,
+    ) -> Result<(), VerificationError> {
+        let signed_commitment = committed_view
+            .signed_commitment
+            .as_ref()
+            .ok_or(VerificationError::MissingSignedCommitment)?;
+        if committed_view.relay_header.number != signed_commitment.commitment.block_number {
+            return Err(VerificationError::BlockNumberMismatch {
+                header: committed_view.relay_header.number.into(),
+                commitment: signed_commitment.commitment.block_number.into(),
+            });
+        }
+
+        self.verify_finalized_para_root(
+            at_mmr_root,
+            committed_view.relay_header.clone(),
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_heads_root,
+        )
+    }
+
+    // Verifies that MMR with given root and number of leaves is a prefix of
+    // the last finalized MMR, so that claims against older root can still be trusted
+    pub fn verify_ancestry(
+        &self,
+        prev_mmr_root: MMRNode<L>,
+        prev_mmr_leaves: u64,
+        ancestry_proof: AncestryProof<L>,
+    ) -> Result<(), VerificationError> {
+        let last_finalized_block = self.finalized_block(None)?;
+
+        let is_ancestor = verify_mmr_ancestry_proof_with_domain::<_, H, D>(
+            prev_mmr_root,
+            prev_mmr_leaves,
+            last_finalized_block.beefy_mmr_root.clone(),
+            last_finalized_block.beefy_mmr_leaves,
+            ancestry_proof,
+        )
+        .map_err(|_| VerificationError::AncestryProofFailed)?;
+        if !is_ancestor {
+            return Err(VerificationError::AncestryProofFailed);
+        }
+
+        Ok(())
+    }
+
+    // Checks done before any proof of a claim is verified
+    fn check_claim_inputs(
+        &self,
+        para_block: &TestHeader,
+        expected_para_number: Option<BlockNumber>,
+        claimed_kv: &(Vec<u8>, Vec<u8>),
+    ) -> Result<(), VerificationError> {
+        self.check_claim_size(claimed_kv)?;
+        if let Some(expected_para_number) = expected_para_number {
+            if BlockNumber(para_block.number) != expected_para_number {
+                return Err(VerificationError::UnexpectedParaBlock {
+                    expected: expected_para_number,
+                    got: para_block.number.into(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_claim_size(&self, claimed_kv: &(Vec<u8>, Vec<u8>)) -> Result<(), VerificationError> {
+        let (key, value) = claimed_kv;
+        let too_long = |len: usize, max: Option<usize>| max.map_or(false, |max| len > max);
+        if too_long(key.len(), self.max_key_len) || too_long(value.len(), self.max_value_len) {
+            return Err(VerificationError::ClaimTooLarge {
+                key_len: key.len(),
+                value_len: value.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn verify_para_block_finalized(
+        &self,
+        at_mmr_root: Option<MMRNode<L>>,
+        at_relay_block: TestHeader,
+        beefy_mmr_proof_items: Vec<MMRNode<L>>,
+        block_pos_in_mmr: MmrPosition,
+        para_id: ParaId,
+        para_block: &TestHeader,
+        para_block_inclusion_proof: Vec<Vec<u8>>,
+        para_block_merkle_root: HashOutput,
+    ) -> Result<(), VerificationError> {
+        self.verify_finalized_para_root(
+            at_mmr_root,
+            at_relay_block,
+            beefy_mmr_proof_items,
+            block_pos_in_mmr,
+            para_block_merkle_root,
+        )?;
+
+        // We now trust the para block merkle root
+        // So, let's check if given para block is indeed part of that merkle root
+        // under the key of the given parachain
+        // if yes, that would mean that para block is finalized
+        // and by extension the storage claim is also finalized.
+        verify_para_head_proof::<H, K>(
+            para_id,
+            para_block,
+            para_block_merkle_root,
+            para_block_inclusion_proof,
+        )
+    }
+}
+
+// Needs the next authority set, which only `LeafData` carries
+impl<H: Hasher<Out = HashOutput>, K: ParaHeadKey, D: MmrDomain>
+    EthereumActor<H, K, LeafData<H>, D>
+{
+    // Adopts authority set announced by a leaf of the finalized MMR (latest one by default),
+    // so that handover can be learned from the MMR instead of the commitment payload.
+    // `next_authorities` must be the set whose merkle root the leaf carries.
+    pub fn adopt_next_authority_set(
+        &mut self,
+        at_mmr_root: Option<MMRNode<LeafData<H>>>,
+        beefy_mmr_proof_items: Vec<MMRNode<LeafData<H>>>,
+        leaf_pos: MmrPosition,
+        leaf: LeafData<H>,
+        next_authorities: Vec<AuthorityId>,
+    ) -> Result<(), VerificationError> {
+        let next_set_id = ValidatorSetId(leaf.next_authority_set_id);
+        if next_set_id != self.current_set_id.next() {
+            return Err(VerificationError::InvalidSetIdTransition {
+                current: self.current_set_id,
+                new: next_set_id,
+            });
+        }
+        if leaf.next_authority_set_len != next_authorities.len() as u32
+            || leaf.next_authority_set_root != build_authority_merkle_root(&next_authorities)
+        {
+            return Err(VerificationError::AuthoritySetMismatch);
+        }
+
+        self.verify_finalized_leaves(
+            at_mmr_root,
+            beefy_mmr_proof_items,
+            vec![(leaf_pos, MMRNode::Data(leaf.clone()))],
+        )?;
+
+        self.events.push(ActorEvent::AuthoritySetChanged {
+            old_set_id: self.current_set_id,
+            new_set_id: next_set_id,
+            diff: authority_set_diff(&self.current_authorities, &next_authorities),
+        });
+        self.current_authority_root = leaf.next_authority_set_root;
+        self.current_authority_set_len = leaf.next_authority_set_len;
+        self.current_authorities = next_authorities;
+        self.current_set_id = next_set_id;
+
+        Ok(())
+    }
+}
+
+// Root and leaf count of the MMR with `root_id`, other than the beefy one, committed by
+// `finalized_block`. Its leaves can not outnumber those of the finalized beefy MMR.
+fn payload_mmr<L: BeefyLeaf>(
+    finalized_block: &EthereumView<L>,
+    root_id: PayloadId,
+) -> Result<(MMRNode<L>, u64), VerificationError> {
+    let (mmr_root, mmr_leaves) = finalized_block
+        .signed_commitment
+        .as_ref()
+        .and_then(|signed_commitment| signed_commitment.commitment.payload.mmr_root_by_id(root_id))
+        .ok_or(VerificationError::MissingPayloadRoot { id: root_id })?;
+    if mmr_leaves > finalized_block.beefy_mmr_leaves {
+        return Err(VerificationError::MmrLeavesOutOfRange {
+            claimed: mmr_leaves,
+            finalized: finalized_block.beefy_mmr_leaves,
+        });
+    }
+    Ok((mmr_root, mmr_leaves))
+}
+
+pub(crate) fn verify_para_head_proof<H: Hasher<Out = HashOutput>, K: ParaHeadKey>(
+    para_id: ParaId,
+    para_block: &TestHeader,
+    para_block_merkle_root: HashOutput,
+    para_block_inclusion_proof: Vec<Vec<u8>>,
+) -> Result<(), VerificationError> {
+    stage_span!("para_inclusion");
+    let items = vec![(K::key(para_id, para_block), Some(para_block.encode()))];
+    if sp_trie::verify_trie_proof::<Layout<H>, _, _, _>(
+        &para_block_merkle_root,
+        &*para_block_inclusion_proof,
+        items.iter(),
+    )
+    .is_err()
+    {
+        return Err(VerificationError::ParaInclusionFailed);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn verify_storage_proof<H: Hasher<Out = HashOutput>>(
+    storage_root: HashOutput,
+    claimed_kv: (Vec<u8>, Vec<u8>),
+    kv_proof: Vec<Vec<u8>>,
+) -> Result<(), VerificationError> {
+    verify_storage_multiproof::<H>(storage_root, vec![claimed_kv], kv_proof)
+}
+
+fn verify_storage_multiproof<H: Hasher<Out = HashOutput>>(
+    storage_root: HashOutput,
+    claimed_kvs: Vec<(Vec<u8>, Vec<u8>)>,
+    kv_proof: Vec<Vec<u8>>,
+) -> Result<(), VerificationError> {
+    stage_span!("storage_proof");
+    let items = claimed_kvs
+        .into_iter()
+        .map(|(key, value)| (key, Some(value)))
+        .collect::<Vec<_>>();
+    if sp_trie::verify_trie_proof::<Layout<H>, _, _, _>(&storage_root, &*kv_proof, items.iter())
+        .is_err()
+    {
+        return Err(VerificationError::StorageProofFailed);
+    }
+
+    Ok(())
+}
+
+fn verify_key_range_proof<H: Hasher<Out = HashOutput>>(
+    storage_root: HashOutput,
+    lo: &[u8],
+    hi: &[u8],
+    claimed_kvs: Vec<(Vec<u8>, Vec<u8>)>,
+    range_proof: Vec<Vec<u8>>,
+) -> Result<(), VerificationError> {
+    stage_span!("storage_proof");
+    let db = sp_trie::StorageProof::new(range_proof).into_memory_db::<H>();
+    let trie = sp_trie::TrieDB::<Layout<H>>::new(&db, &storage_root)
+        .map_err(|_| VerificationError::StorageProofFailed)?;
+    let iter = sp_trie::TrieDBIterator::new_prefixed_then_seek(&trie, &[], lo)
+        .map_err(|_| VerificationError::StorageProofFailed)?;
+
+    // Iteration stops at the first key past `hi`, which proves nothing else is in range
+    let mut kvs = Vec::new();
+    for item in iter {
+        let (key, value) = item.map_err(|_| VerificationError::StorageProofFailed)?;
+        if &key[..] > hi {
+            break;
+        }
+        kvs.push((key, value));
+    }
+
+    if kvs != claimed_kvs {
+        return Err(VerificationError::KeyRangeMismatch);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_generation::{
+        create_child_block_with_storage, create_genesis_block_with_paras,
+        create_random_child_block_with_scheme, create_random_child_block_with_store,
+        generate_random_storage_and_proof, generate_random_storage_and_range_proof,
+        generate_signed_commitment, ChainBuilder, DEFAULT_PARA_ID,
+    };
+    use crate::demo::generate_beefy_pairs_from_seed;
+    use crate::mmr::{encode_mmr_proof, mmr_from_store};
+    use crate::utils::{
+        build_para_heads_trie, empty_mmr_root, generate_signer_multiproof, mmr_root_digest_item,
+        prove_para_head_inclusion, RealStorageKeyed,
+    };
+    use beefy_primitives::crypto::Pair;
+    use codec::Decode;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use sp_core::KeccakHasher;
+
+    fn authority_ids(authorities: &[(Pair, AuthorityId)]) -> Vec<AuthorityId> {
+        authorities.iter().map(|(_, id)| id.clone()).collect()
+    }
+
+    fn from_checkpoint(
+        checkpoint: EthereumView<LeafData>,
+        trusted_authorities: Vec<AuthorityId>,
+    ) -> Result<EthereumActor, VerificationError> {
+        EthereumActor::from_checkpoint(checkpoint, trusted_authorities, ValidatorSetId(0), 10, None)
+    }
+
+    #[test]
+    fn checkpoint_signed_by_trusted_set_is_adopted() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block_with_commitment()
+            .push_block_with_commitment()
+            .build();
+
+        let mut actor = from_checkpoint(blocks[2].ethereum_view(), ids.clone()).unwrap();
+        assert_eq!(actor.last_finalized_number(), Some(BlockNumber(3)));
+        assert_eq!(actor.current_authorities(), &ids[..]);
+        assert_eq!(
+            actor.ingest_new_header(
+                blocks[3].ethereum_view(),
+                generate_signer_proofs(&ids, &[0, 1, 2, 3, 4])
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn checkpoint_not_signed_by_trusted_set_is_rejected() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+
+        let others = authority_ids(&generate_beefy_pairs_from_seed(5, 1));
+        assert_eq!(
+            from_checkpoint(blocks[2].ethereum_view(), others)
+                .map(|actor| actor.last_finalized_number()),
+            Err(VerificationError::SignatureInvalid)
+        );
+        assert_eq!(
+            from_checkpoint(blocks[1].ethereum_view(), ids)
+                .map(|actor| actor.last_finalized_number()),
+            Err(VerificationError::MissingSignedCommitment)
+        );
+    }
+
+    #[test]
+    fn multiproof_of_seven_out_of_ten_signers_is_ingested() {
+        let authorities = generate_beefy_pairs_from_seed(10, 0);
+        let ids = authority_ids(&authorities);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block_with_commitment()
+            .build();
+        let view = blocks[1].ethereum_view();
+        let signatures = view.signed_commitment.as_ref().unwrap().signatures.clone();
+        let signer_indices = [0, 1, 2, 3, 5, 7, 9];
+
+        let multiproof = generate_signer_multiproof(&ids, &signatures, &signer_indices);
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        assert_eq!(
+            actor.ingest_new_header_with_multiproof(view, &multiproof.encode()),
+            Ok(())
+        );
+        assert_eq!(actor.last_finalized_number(), Some(BlockNumber(2)));
+        assert_eq!(actor.last_signers().unwrap().len(), signer_indices.len());
+    }
+
+    #[test]
+    fn multiproof_with_any_tampered_signer_is_rejected() {
+        let authorities = generate_beefy_pairs_from_seed(10, 0);
+        let ids = authority_ids(&authorities);
+        let outsider = generate_beefy_pairs_from_seed(1, 1).remove(0).1;
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block_with_commitment()
+            .build();
+        let view = blocks[1].ethereum_view();
+        let signatures = view.signed_commitment.as_ref().unwrap().signatures.clone();
+        let signer_indices = [0, 1, 2, 3, 5, 7, 9];
+
+        for tampered in 0..signer_indices.len() {
+            let mut multiproof = generate_signer_multiproof(&ids, &signatures, &signer_indices);
+            multiproof.signers[tampered].1 = outsider.clone();
+
+            let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+            assert_eq!(
+                actor.ingest_new_header_with_multiproof(view.clone(), &multiproof.encode()),
+                Err(VerificationError::SignerMultiproofInvalid)
+            );
+            assert_eq!(actor.last_finalized_number(), None);
+        }
+    }
+
+    #[test]
+    fn keccak_signed_chain_needs_keccak_actor() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .with_signing_scheme(SigningScheme::Keccak256)
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        let signer_proofs = generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]);
+
+        let mut raw_actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        assert_eq!(
+            raw_actor.ingest_new_header(blocks[2].ethereum_view(), signer_proofs.clone()),
+            Err(VerificationError::SignatureInvalid)
+        );
+
+        let mut keccak_actor = EthereumActor::from_genesis(&blocks[0], 10, None)
+            .with_signing_scheme(SigningScheme::Keccak256);
+        assert_eq!(
+            keccak_actor.ingest_new_header(blocks[2].ethereum_view(), signer_proofs),
+            Ok(())
+        );
+        assert_eq!(keccak_actor.last_finalized_number(), Some(BlockNumber(3)));
+    }
+
+    // Commitments at relay blocks 3, 5, 7 and 9
+    fn chain_with_commitments() -> (Vec<BlockData>, Vec<SignerProof>) {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block_with_commitment()
+            .push_block()
+            .push_block_with_commitment()
+            .push_block()
+            .push_block_with_commitment()
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        (blocks, generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]))
+    }
+
+    #[test]
+    fn fresh_commitment_is_accepted() {
+        let (blocks, signer_proofs) = chain_with_commitments();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, Some(3));
+
+        assert_eq!(
+            actor.ingest_new_header(blocks[2].ethereum_view(), signer_proofs.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            actor.ingest_new_header(blocks[8].ethereum_view(), signer_proofs),
+            Ok(())
+        );
+        assert_eq!(actor.last_finalized_number(), Some(BlockNumber(9)));
+    }
+
+    #[test]
+    fn stale_commitment_is_rejected() {
+        let (blocks, signer_proofs) = chain_with_commitments();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, Some(3));
+        for block in [&blocks[2], &blocks[8]].iter() {
+            actor
+                .ingest_new_header(block.ethereum_view(), signer_proofs.clone())
+                .unwrap();
+        }
+
+        // Replay of an ingested commitment is stale as well
+        for (stale, number) in [(&blocks[2], 3), (&blocks[4], 5)].iter() {
+            assert_eq!(
+                actor.ingest_new_header(stale.ethereum_view(), signer_proofs.clone()),
+                Err(VerificationError::StaleCommitment {
+                    last_finalized: BlockNumber(9),
+                    got: BlockNumber(*number),
+                })
+            );
+        }
+        // Within staleness bound, but still behind the last finalized block
+        assert_eq!(
+            actor.ingest_new_header(blocks[6].ethereum_view(), signer_proofs.clone()),
+            Err(VerificationError::CommitmentGoesBackwards {
+                last_finalized: BlockNumber(9),
+                got: BlockNumber(7),
+            })
+        );
+
+        // Without staleness bound replay is reported as such
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        for block in [&blocks[2], &blocks[8]].iter() {
+            actor
+                .ingest_new_header(block.ethereum_view(), signer_proofs.clone())
+                .unwrap();
+        }
+        assert_eq!(
+            actor.ingest_new_header(blocks[2].ethereum_view(), signer_proofs),
+            Err(VerificationError::AlreadyIngested)
+        );
+    }
+
+    // Chain with commitments at relay blocks 3 and 5
+    fn chain_to_check() -> (Vec<BlockData>, Vec<SignerProof>) {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block_with_commitment()
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        (blocks, generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]))
+    }
+
+    #[test]
+    fn checked_commitment_is_not_ingested() {
+        let (blocks, signer_proofs) = chain_to_check();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        let view = blocks[2].ethereum_view();
+
+        assert_eq!(actor.check_commitment(&view, &signer_proofs), Ok(()));
+        assert_eq!(actor.last_finalized_number(), None);
+        assert_eq!(actor.ingest_new_header(view, signer_proofs), Ok(()));
+    }
+
+    #[test]
+    fn check_rejects_what_ingestion_rejects() {
+        let (blocks, signer_proofs) = chain_to_check();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(blocks[2].ethereum_view(), signer_proofs.clone())
+            .unwrap();
+
+        assert_eq!(
+            actor.check_commitment(&blocks[2].ethereum_view(), &signer_proofs),
+            Err(VerificationError::AlreadyIngested)
+        );
+        assert_eq!(
+            actor.check_commitment(&blocks[3].ethereum_view(), &signer_proofs),
+            Err(VerificationError::MissingSignedCommitment)
+        );
+        assert_eq!(actor.last_finalized_number(), Some(BlockNumber(3)));
+    }
+
+    #[test]
+    fn claims_are_provable_up_to_parent_of_finalized_block() {
+        let (blocks, signer_proofs) = chain_with_commitments();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(blocks[4].ethereum_view(), signer_proofs.clone())
+            .unwrap();
+
+        // Relay blocks 1 to 4 are leaves of MMR of finalized relay block 5
+        for position in 0..4 {
+            let claim_proof = blocks[position].build_claim_proof(&blocks[position + 1], &blocks[4]);
+            assert_eq!(actor.verify_claim_proof(None, &claim_proof), Ok(()));
+        }
+
+        // Leaf of the finalized block itself is appended by its child
+        let claim_proof = blocks[4].build_claim_proof(&blocks[5], &blocks[6]);
+        assert_eq!(
+            actor.verify_claim_proof(None, &claim_proof),
+            Err(VerificationError::NotYetFinalized {
+                last_finalized: BlockNumber(5),
+                claimed: BlockNumber(5),
+            })
+        );
+        actor
+            .ingest_new_header(blocks[6].ethereum_view(), signer_proofs)
+            .unwrap();
+        assert_eq!(actor.verify_claim_proof(None, &claim_proof), Ok(()));
+    }
+
+    const SECOND_MMR_ID: PayloadId = *b"m2";
+
+    // Commitment of relay block 7 carries, along with the beefy MMR root, the beefy
+    // MMR root of relay block 4 (i.e. its first 3 leaves) committed to have `leaves`
+    fn actor_with_second_mmr(leaves: u64) -> (EthereumActor, Vec<BlockData>) {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let pairs = authorities
+            .iter()
+            .map(|(pair, _)| pair.clone())
+            .collect::<Vec<_>>();
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block()
+            .push_block()
+            .push_block()
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+
+        let mut view = blocks[6].ethereum_view();
+        let commitment = &view.signed_commitment.as_ref().unwrap().commitment;
+        let signed_commitment = generate_signed_commitment(
+            commitment.validator_set_id,
+            commitment.block_number,
+            commitment.payload.clone().with_mmr_root(
+                SECOND_MMR_ID,
+                blocks[3].ethereum_view().beefy_mmr_root,
+                leaves,
+            ),
+            &pairs,
+            &[],
+            SigningScheme::Raw,
+        );
+        view.signed_commitment = Some(signed_commitment);
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(view, generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]))
+            .unwrap();
+        (actor, blocks)
+    }
+
+    fn verify_with_root_id(
+        actor: &EthereumActor,
+        claim_proof: &ClaimProof,
+        root_id: PayloadId,
+        expected_para_number: Option<BlockNumber>,
+    ) -> Result<(), VerificationError> {
+        let claim_proof = claim_proof.clone();
+        actor.verify_claim(
+            None,
+            root_id,
+            claim_proof.relay_header,
+            claim_proof.mmr_proof_items,
+            claim_proof.block_pos_in_mmr,
+            claim_proof.para_id,
+            claim_proof.para_header,
+            expected_para_number,
+            claim_proof.para_header_inclusion_proof,
+            claim_proof.para_header_merkle_root,
+            claim_proof.claimed_kv,
+            claim_proof.kv_proof,
+        )
+    }
+
+    #[test]
+    fn claim_verifies_against_its_own_root_only() {
+        let (actor, blocks) = actor_with_second_mmr(3);
+
+        let in_second_mmr = blocks[1].build_claim_proof(&blocks[2], &blocks[3]);
+        let in_beefy_mmr = blocks[1].build_claim_proof(&blocks[2], &blocks[6]);
+        assert_eq!(
+            verify_with_root_id(&actor, &in_second_mmr, SECOND_MMR_ID, None),
+            Ok(())
+        );
+        assert_eq!(
+            verify_with_root_id(&actor, &in_beefy_mmr, MMR_ROOT_ID, None),
+            Ok(())
+        );
+        assert!(verify_with_root_id(&actor, &in_second_mmr, MMR_ROOT_ID, None).is_err());
+        assert!(verify_with_root_id(&actor, &in_beefy_mmr, SECOND_MMR_ID, None).is_err());
+
+        let para_number = BlockNumber(in_second_mmr.para_header.number);
+        assert_eq!(
+            verify_with_root_id(
+                &actor,
+                &in_second_mmr,
+                SECOND_MMR_ID,
+                Some(BlockNumber(para_number.0 + 1))
+            ),
+            Err(VerificationError::UnexpectedParaBlock {
+                expected: BlockNumber(para_number.0 + 1),
+                got: para_number,
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_or_miscommitted_root_id_is_rejected() {
+        let (actor, blocks) = actor_with_second_mmr(3);
+        let in_second_mmr = blocks[1].build_claim_proof(&blocks[2], &blocks[3]);
+        assert_eq!(
+            verify_with_root_id(&actor, &in_second_mmr, *b"m3", None),
+            Err(VerificationError::MissingPayloadRoot { id: *b"m3" })
+        );
+
+        // Leaf count committed along with the root does not match the root
+        let (actor, blocks) = actor_with_second_mmr(4);
+        let in_second_mmr = blocks[1].build_claim_proof(&blocks[2], &blocks[3]);
+        assert!(verify_with_root_id(&actor, &in_second_mmr, SECOND_MMR_ID, None).is_err());
+
+        // Leaf count is bounded by the finalized beefy MMR
+        let (actor, blocks) = actor_with_second_mmr(7);
+        let in_second_mmr = blocks[1].build_claim_proof(&blocks[2], &blocks[3]);
+        assert_eq!(
+            verify_with_root_id(&actor, &in_second_mmr, SECOND_MMR_ID, None),
+            Err(VerificationError::MmrLeavesOutOfRange {
+                claimed: 7,
+                finalized: 6,
+            })
+        );
+    }
+
+    fn verify_with_leaf_hash(
+        actor: &EthereumActor,
+        claim_proof: ClaimProof,
+    ) -> Result<HashOutput, VerificationError> {
+        actor.verify_claim_with_leaf_hash(
+            None,
+            claim_proof.relay_header,
+            claim_proof.mmr_proof_items,
+            claim_proof.block_pos_in_mmr,
+            claim_proof.para_id,
+            claim_proof.para_header,
+            None,
+            claim_proof.para_header_inclusion_proof,
+            claim_proof.para_header_merkle_root,
+            claim_proof.claimed_kv,
+            claim_proof.kv_proof,
+        )
+    }
+
+    #[test]
+    fn verified_claim_returns_its_leaf_hash() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                blocks[3].ethereum_view(),
+                generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+
+        // Leaf of every block in the finalized MMR is returned
+        for position in 0..3 {
+            let leaf = LeafData::<HashingAlgo>::from_relay_block(
+                &blocks[position].relay_header,
+                blocks[position + 1].para_header_merkle_root,
+            )
+            .unwrap();
+            let claim_proof = blocks[position].build_claim_proof(&blocks[position + 1], &blocks[3]);
+            assert_eq!(verify_with_leaf_hash(&actor, claim_proof), Ok(leaf.hash()));
+        }
+    }
+
+    #[test]
+    fn leaf_hash_is_not_returned_for_unproven_leaf() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                blocks[2].ethereum_view(),
+                generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+        // Leaf built with this para heads root is not in the finalized MMR
+        let mut claim_proof = blocks[1].build_claim_proof(&blocks[2], &blocks[2]);
+        claim_proof.para_header_merkle_root = HashOutput::repeat_byte(1);
+
+        assert_eq!(
+            verify_with_leaf_hash(&actor, claim_proof),
+            Err(VerificationError::MmrProofFailed)
+        );
+    }
+
+    // Actor finalizing relay block 7 and claim proof of para block 5, that of relay block 5
+    fn finalized_para_block_five() -> (EthereumActor, ClaimProof) {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let mut chain = ChainBuilder::with_seed(authorities, 0);
+        for _ in 0..5 {
+            chain = chain.push_block();
+        }
+        let blocks = chain.push_block_with_commitment().build();
+
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                blocks[6].ethereum_view(),
+                generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+        let claim_proof = blocks[4].build_claim_proof(&blocks[5], &blocks[6]);
+        assert_eq!(claim_proof.para_header.number, 5);
+        (actor, claim_proof)
+    }
+
+    fn verify_finality(
+        actor: &EthereumActor,
+        claim_proof: ClaimProof,
+        with_storage_claim: bool,
+    ) -> Result<(), VerificationError> {
+        let storage_claim = if with_storage_claim {
+            Some((claim_proof.claimed_kv, claim_proof.kv_proof))
+        } else {
+            None
+        };
+        actor.verify_finality_only(
+            None,
+            claim_proof.relay_header,
+            claim_proof.mmr_proof_items,
+            claim_proof.block_pos_in_mmr,
+            claim_proof.para_id,
+            claim_proof.para_header,
+            claim_proof.para_header_inclusion_proof,
+            claim_proof.para_header_merkle_root,
+            storage_claim,
+        )
+    }
+
+    #[test]
+    fn finality_is_proven_with_or_without_storage_claim() {
+        let (actor, claim_proof) = finalized_para_block_five();
+
+        assert_eq!(verify_finality(&actor, claim_proof.clone(), false), Ok(()));
+        assert_eq!(verify_finality(&actor, claim_proof, true), Ok(()));
+    }
+
+    #[test]
+    fn finality_only_still_rejects_bad_proofs() {
+        let (actor, claim_proof) = finalized_para_block_five();
+
+        // Storage proof is skipped, but checked when given
+        let mut bad_storage_claim = claim_proof.clone();
+        bad_storage_claim.claimed_kv.1 = vec![1, 2, 3];
+        assert_eq!(
+            verify_finality(&actor, bad_storage_claim.clone(), false),
+            Ok(())
+        );
+        assert_eq!(
+            verify_finality(&actor, bad_storage_claim, true),
+            Err(VerificationError::StorageProofFailed)
+        );
+
+        let mut bad_para_root = claim_proof;
+        bad_para_root.para_header_merkle_root = HashOutput::repeat_byte(1);
+        assert_eq!(
+            verify_finality(&actor, bad_para_root, false),
+            Err(VerificationError::MmrProofFailed)
+        );
+    }
+
+    #[test]
+    fn para_root_is_verified_without_para_block() {
+        let (actor, claim_proof) = finalized_para_block_five();
+        let verify = |para_root: HashOutput| {
+            actor.verify_finalized_para_root(
+                None,
+                claim_proof.relay_header.clone(),
+                claim_proof.mmr_proof_items.clone(),
+                claim_proof.block_pos_in_mmr,
+                para_root,
+            )
+        };
+
+        assert_eq!(verify(claim_proof.para_header_merkle_root), Ok(()));
+        assert_eq!(
+            verify(HashOutput::repeat_byte(1)),
+            Err(VerificationError::MmrProofFailed)
+        );
+    }
+
+    #[test]
+    fn position_outside_of_finalized_leaves_is_rejected() {
+        let (actor, claim_proof) = finalized_para_block_five();
+        // Finalized MMR has 6 leaves, i.e. 10 nodes
+        let verify_at = |position: u64| {
+            let mut claim_proof = claim_proof.clone();
+            claim_proof.block_pos_in_mmr = MmrPosition(position);
+            actor.verify_claim_proof(None, &claim_proof)
+        };
+
+        assert_eq!(
+            verify_at(10),
+            Err(VerificationError::PositionOutOfRange {
+                position: 10,
+                mmr_size: 10,
+            })
+        );
+        // Position of an inner node rather than a leaf
+        assert_eq!(
+            verify_at(2),
+            Err(VerificationError::PositionOutOfRange {
+                position: 2,
+                mmr_size: 10,
+            })
+        );
+        assert_eq!(verify_at(claim_proof.block_pos_in_mmr.0), Ok(()));
+    }
+
+    #[test]
+    fn claim_within_limits_is_accepted() {
+        let (actor, claim_proof) = finalized_para_block_five();
+        // Generated storage has 32 byte keys and 64 byte values
+        let actor = actor.with_claim_limits(Some(32), Some(64));
+
+        assert_eq!(actor.verify_claim_proof(None, &claim_proof), Ok(()));
+        assert_eq!(verify_finality(&actor, claim_proof, true), Ok(()));
+    }
+
+    #[test]
+    fn claim_over_limits_is_rejected() {
+        let (actor, claim_proof) = finalized_para_block_five();
+        let too_large = Err(VerificationError::ClaimTooLarge {
+            key_len: 32,
+            value_len: 64,
+        });
+
+        let actor = actor.with_claim_limits(Some(31), None);
+        assert_eq!(actor.verify_claim_proof(None, &claim_proof), too_large);
+        let actor = actor.with_claim_limits(None, Some(63));
+        assert_eq!(actor.verify_claim_proof(None, &claim_proof), too_large);
+        assert_eq!(
+            actor.verify_storage_against_root(
+                claim_proof.para_header.state_root,
+                claim_proof.claimed_kv.clone(),
+                claim_proof.kv_proof.clone(),
+            ),
+            too_large
+        );
+        assert_eq!(verify_finality(&actor, claim_proof, true), too_large);
+    }
+
+    #[test]
+    fn multi_megabyte_value_is_rejected_by_default() {
+        let (actor, mut claim_proof) = finalized_para_block_five();
+        claim_proof.claimed_kv.1 = vec![0; 4 * 1024 * 1024];
+
+        assert_eq!(
+            actor.verify_claim_proof(None, &claim_proof),
+            Err(VerificationError::ClaimTooLarge {
+                key_len: 32,
+                value_len: 4 * 1024 * 1024,
+            })
+        );
+        assert_eq!(
+            verify_finality(&actor, claim_proof.clone(), true),
+            Err(VerificationError::ClaimTooLarge {
+                key_len: 32,
+                value_len: 4 * 1024 * 1024,
+            })
+        );
+
+        // Without limits the value reaches the storage proof, which it does not match
+        let actor = actor.with_claim_limits(None, None);
+        assert_eq!(
+            actor.verify_claim_proof(None, &claim_proof),
+            Err(VerificationError::StorageProofFailed)
+        );
+    }
+
+    // Chain with commitments at relay blocks 3 and 9, along with the actor finalizing both
+    fn actor_finalizing_three_and_nine() -> (EthereumActor, Vec<BlockData>) {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let signer_proofs = generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]);
+        let mut chain = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block_with_commitment();
+        for _ in 0..5 {
+            chain = chain.push_block();
+        }
+        let blocks = chain.push_block_with_commitment().build();
+
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        for block in [&blocks[2], &blocks[8]].iter() {
+            actor
+                .ingest_new_header(block.ethereum_view(), signer_proofs.clone())
+                .unwrap();
+        }
+        (actor, blocks)
+    }
+
+    // Commitment of `committed_view` proven against MMR of relay block 9, using MMR proof
+    // of relay block 3 and given para heads root of its leaf
+    fn verify_commitment_in_mmr(
+        actor: &EthereumActor,
+        blocks: &[BlockData],
+        committed_view: &EthereumView<LeafData>,
+        para_heads_root: HashOutput,
+    ) -> Result<(), VerificationError> {
+        let claim_proof = blocks[2].build_claim_proof(&blocks[3], &blocks[8]);
+        actor.verify_commitment_block_in_mmr(
+            committed_view,
+            None,
+            claim_proof.mmr_proof_items,
+            claim_proof.block_pos_in_mmr,
+            para_heads_root,
+        )
+    }
+
+    #[test]
+    fn committed_block_is_found_in_later_mmr() {
+        let (actor, blocks) = actor_finalizing_three_and_nine();
+
+        assert_eq!(
+            verify_commitment_in_mmr(
+                &actor,
+                &blocks,
+                &blocks[2].ethereum_view(),
+                blocks[3].para_header_merkle_root
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn committed_block_not_in_later_mmr_is_rejected() {
+        let (actor, blocks) = actor_finalizing_three_and_nine();
+        let committed_view = blocks[2].ethereum_view();
+        let para_heads_root = blocks[3].para_header_merkle_root;
+
+        assert_eq!(
+            verify_commitment_in_mmr(&actor, &blocks, &committed_view, HashOutput::repeat_byte(1)),
+            Err(VerificationError::MmrProofFailed)
+        );
+        assert_eq!(
+            verify_commitment_in_mmr(&actor, &blocks, &blocks[1].ethereum_view(), para_heads_root),
+            Err(VerificationError::MissingSignedCommitment)
+        );
+        let mut mismatched_view = committed_view;
+        mismatched_view.relay_header = blocks[3].relay_header.clone();
+        assert_eq!(
+            verify_commitment_in_mmr(&actor, &blocks, &mismatched_view, para_heads_root),
+            Err(VerificationError::BlockNumberMismatch {
+                header: BlockNumber(4),
+                commitment: BlockNumber(3),
+            })
+        );
+    }
+
+    // Relay blocks 10 down to `target`, with MMR proof of every older block against the
+    // MMR of the block after it, and the actor finalizing block 10
+    fn relay_chain_down_to(
+        target: usize,
+    ) -> (
+        EthereumActor,
+        Vec<BlockData>,
+        Vec<EthereumView>,
+        Vec<(MmrPosition, Vec<MMRNode<LeafData>>)>,
+    ) {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let mut chain = ChainBuilder::with_seed(authorities, 0);
+        for _ in 0..8 {
+            chain = chain.push_block();
+        }
+        let blocks = chain.push_block_with_commitment().build();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                blocks[9].ethereum_view(),
+                generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+
+        let relay_chain = (target - 1..10)
+            .rev()
+            .map(|i| blocks[i].ethereum_view())
+            .collect();
+        let mmr_proofs = (target - 1..9)
+            .rev()
+            .map(|i| {
+                let proof = blocks[i].build_claim_proof(&blocks[i + 1], &blocks[i + 1]);
+                (proof.block_pos_in_mmr, proof.mmr_proof_items)
+            })
+            .collect();
+        (actor, blocks, relay_chain, mmr_proofs)
+    }
+
+    fn verify_via_chain(
+        actor: &EthereumActor,
+        target: &BlockData,
+        child_of_target: &BlockData,
+        relay_chain: Vec<EthereumView>,
+        mmr_proofs: Vec<(MmrPosition, Vec<MMRNode<LeafData>>)>,
+    ) -> Result<(), VerificationError> {
+        let claim_proof = target.build_claim_proof(child_of_target, child_of_target);
+        actor.verify_claim_via_chain(
+            None,
+            relay_chain,
+            mmr_proofs,
+            claim_proof.para_id,
+            claim_proof.para_header,
+            claim_proof.para_header_inclusion_proof,
+            claim_proof.claimed_kv,
+            claim_proof.kv_proof,
+        )
+    }
+
+    #[test]
+    fn block_three_is_proven_from_tip_at_block_ten() {
+        let (actor, blocks, relay_chain, mmr_proofs) = relay_chain_down_to(3);
+        assert_eq!(relay_chain.len(), 8);
+        assert_eq!(
+            verify_via_chain(&actor, &blocks[2], &blocks[3], relay_chain, mmr_proofs),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn hop_not_proven_against_previous_hop_is_rejected() {
+        // Proof of block 5 against the finalized MMR instead of the MMR of block 6
+        let (actor, blocks, relay_chain, mut mmr_proofs) = relay_chain_down_to(3);
+        let proof = blocks[4].build_claim_proof(&blocks[5], &blocks[9]);
+        mmr_proofs[4] = (proof.block_pos_in_mmr, proof.mmr_proof_items);
+        assert!(verify_via_chain(&actor, &blocks[2], &blocks[3], relay_chain, mmr_proofs).is_err());
+
+        // Gap in the chain
+        let (actor, blocks, mut relay_chain, mut mmr_proofs) = relay_chain_down_to(3);
+        relay_chain.remove(3);
+        mmr_proofs.remove(3);
+        assert_eq!(
+            verify_via_chain(&actor, &blocks[2], &blocks[3], relay_chain, mmr_proofs),
+            Err(VerificationError::InvalidRelayChain)
+        );
+
+        // Target proven, but the para block is not the target's
+        let (actor, blocks, relay_chain, mmr_proofs) = relay_chain_down_to(3);
+        assert_eq!(
+            verify_via_chain(&actor, &blocks[3], &blocks[4], relay_chain, mmr_proofs),
+            Err(VerificationError::ParaInclusionFailed)
+        );
+    }
+
+    #[test]
+    fn highest_signed_candidate_is_chosen() {
+        let (blocks, signer_proofs) = chain_with_commitments();
+        let actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        let candidates = vec![
+            (blocks[2].ethereum_view(), signer_proofs.clone()),
+            (blocks[4].ethereum_view(), signer_proofs.clone()),
+            // Highest, but not signed
+            (blocks[5].ethereum_view(), signer_proofs),
+        ];
+
+        assert_eq!(actor.choose_best(&candidates), Some(1));
+        assert_eq!(actor.choose_best(&candidates[2..]), None);
+    }
+
+    #[test]
+    fn handover_is_preferred_at_equal_height() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let signer_proofs = generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]);
+        let without_handover = ChainBuilder::with_seed(authorities.clone(), 0)
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        let with_handover = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .rotate_authorities(generate_beefy_pairs_from_seed(5, 1))
+            .push_block_with_commitment()
+            .build();
+        let actor = EthereumActor::from_genesis(&without_handover[0], 10, None);
+
+        let candidates = vec![
+            (without_handover[2].ethereum_view(), signer_proofs.clone()),
+            (with_handover[2].ethereum_view(), signer_proofs.clone()),
+        ];
+        assert_eq!(actor.choose_best(&candidates), Some(1));
+        let candidates = vec![
+            (with_handover[2].ethereum_view(), signer_proofs.clone()),
+            (without_handover[2].ethereum_view(), signer_proofs),
+        ];
+        assert_eq!(actor.choose_best(&candidates), Some(0));
+    }
+
+    // Views of relay blocks 3, 4 (enacting a handover) and 6, with their signer proofs
+    #[cfg(feature = "parallel")]
+    fn batch_with_handover() -> (Vec<BlockData>, Vec<(EthereumView, Vec<SignerProof>)>) {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let next_authorities = generate_beefy_pairs_from_seed(6, 1);
+        let proofs = generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]);
+        let next_proofs =
+            generate_signer_proofs(&authority_ids(&next_authorities), &[0, 1, 2, 3, 4, 5]);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block_with_commitment()
+            .rotate_authorities(next_authorities)
+            .push_block_with_commitment()
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        let views = vec![
+            (blocks[2].ethereum_view(), proofs.clone()),
+            (blocks[3].ethereum_view(), proofs),
+            (blocks[5].ethereum_view(), next_proofs),
+        ];
+        (blocks, views)
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn batch_ingestion_matches_sequential_ingestion() {
+        let (blocks, views) = batch_with_handover();
+        let mut sequential = EthereumActor::from_genesis(&blocks[0], 10, None);
+        for (view, signer_proofs) in views.clone() {
+            sequential.ingest_new_header(view, signer_proofs).unwrap();
+        }
+        let mut batched = EthereumActor::from_genesis(&blocks[0], 10, None);
+        batched.ingest_batch(views).unwrap();
+
+        assert_eq!(batched.last_finalized_number(), Some(BlockNumber(6)));
+        assert_eq!(
+            batched.last_finalized_number(),
+            sequential.last_finalized_number()
+        );
+        assert_eq!(batched.current_set_id(), sequential.current_set_id());
+        assert_eq!(
+            batched.current_authorities(),
+            sequential.current_authorities()
+        );
+        assert_eq!(batched.last_signers(), sequential.last_signers());
+        assert_eq!(batched.drain_events(), sequential.drain_events());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn batch_with_bad_last_view_leaves_actor_unchanged() {
+        let (blocks, mut views) = batch_with_handover();
+        // Signatures are valid, but the view does not carry the committed MMR root
+        views[2].0.beefy_mmr_root = blocks[4].ethereum_view().beefy_mmr_root;
+
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        assert_eq!(
+            actor.ingest_batch(views),
+            Err(VerificationError::MmrRootMismatch)
+        );
+        assert_eq!(actor.last_finalized_number(), None);
+        assert_eq!(actor.current_set_id(), ValidatorSetId(0));
+        assert_eq!(actor.last_signers(), None);
+        assert_eq!(actor.drain_events(), vec![]);
+    }
+
+    // Actor finalizing relay block 4 and claim proof of relay block 3, whose para block
+    // has given storage (state root, chosen kv pair and its proof)
+    fn finalized_claim_with_storage(
+        storage: (HashOutput, (Vec<u8>, Vec<u8>), Vec<Vec<u8>>),
+        rng: &mut StdRng,
+    ) -> (EthereumActor, ClaimProof) {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let blocks = ChainBuilder::with_seed(authorities, 0).push_block().build();
+        let block = create_child_block_with_storage(
+            &blocks[1],
+            false,
+            None,
+            SigningScheme::Raw,
+            storage,
+            rng,
+        );
+        let finalized =
+            create_random_child_block_with_scheme(&block, true, None, SigningScheme::Raw, rng);
+
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                finalized.ethereum_view(),
+                generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+        (actor, block.build_claim_proof(&finalized, &finalized))
+    }
+
+    // Same as `finalized_claim_with_storage`, along with a range proof of the 5th
+    // to the 10th smallest key of the para block storage
+    fn finalized_key_range() -> (
+        EthereumActor,
+        ClaimProof,
+        Vec<(Vec<u8>, Vec<u8>)>,
+        Vec<Vec<u8>>,
+    ) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (storage_db, storage_root, range_kvs, range_proof) =
+            generate_random_storage_and_range_proof::<HashingAlgo>(&mut rng, 20, 5, 10);
+        let chosen_kv_proof = sp_trie::generate_trie_proof::<Layout<HashingAlgo>, _, _, _>(
+            &storage_db,
+            storage_root,
+            &[range_kvs[0].0.clone()],
+        )
+        .unwrap();
+
+        let (actor, claim_proof) = finalized_claim_with_storage(
+            (storage_root, range_kvs[0].clone(), chosen_kv_proof),
+            &mut rng,
+        );
+        (actor, claim_proof, range_kvs, range_proof)
+    }
+
+    // Same as `finalized_claim_with_storage`, along with a key absent from the para block
+    // storage and its non-inclusion proof
+    fn finalized_absent_key() -> (EthereumActor, ClaimProof, Vec<u8>, Vec<Vec<u8>>) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (storage_db, storage_root, kvs, _) =
+            generate_random_storage_and_range_proof::<HashingAlgo>(&mut rng, 20, 0, 0);
+        let proof = |key: &Vec<u8>| {
+            sp_trie::generate_trie_proof::<Layout<HashingAlgo>, _, _, _>(
+                &storage_db,
+                storage_root,
+                &[key.clone()],
+            )
+            .unwrap()
+        };
+        // Generated keys are random, so this one is not among them
+        let absent_key = vec![0u8; 32];
+        let absence_proof = proof(&absent_key);
+
+        let (actor, claim_proof) = finalized_claim_with_storage(
+            (storage_root, kvs[0].clone(), proof(&kvs[0].0)),
+            &mut rng,
+        );
+        (actor, claim_proof, absent_key, absence_proof)
+    }
+
+    fn verify_absence(
+        actor: &EthereumActor,
+        claim_proof: &ClaimProof,
+        key: Vec<u8>,
+        absence_proof: Vec<Vec<u8>>,
+    ) -> Result<(), VerificationError> {
+        let claim_proof = claim_proof.clone();
+        actor.verify_absence_claim(
+            None,
+            claim_proof.relay_header,
+            claim_proof.mmr_proof_items,
+            claim_proof.block_pos_in_mmr,
+            claim_proof.para_id,
+            claim_proof.para_header,
+            claim_proof.para_header_inclusion_proof,
+            claim_proof.para_header_merkle_root,
+            key,
+            absence_proof,
+        )
+    }
+
+    #[test]
+    fn absent_key_is_proven_absent() {
+        let (actor, claim_proof, absent_key, absence_proof) = finalized_absent_key();
+        assert_eq!(
+            verify_absence(&actor, &claim_proof, absent_key, absence_proof),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn existing_key_is_not_proven_absent() {
+        let (actor, claim_proof, _, _) = finalized_absent_key();
+        assert_eq!(
+            verify_absence(
+                &actor,
+                &claim_proof,
+                claim_proof.claimed_kv.0.clone(),
+                claim_proof.kv_proof.clone()
+            ),
+            Err(VerificationError::KeyExists)
+        );
+    }
+
+    #[test]
+    fn absent_key_over_limit_is_rejected() {
+        let (actor, claim_proof, absent_key, absence_proof) = finalized_absent_key();
+
+        let actor = actor.with_claim_limits(Some(32), Some(0));
+        assert_eq!(
+            verify_absence(
+                &actor,
+                &claim_proof,
+                absent_key.clone(),
+                absence_proof.clone()
+            ),
+            Ok(())
+        );
+        let actor = actor.with_claim_limits(Some(31), None);
+        assert_eq!(
+            verify_absence(&actor, &claim_proof, absent_key, absence_proof),
+            Err(VerificationError::ClaimTooLarge {
+                key_len: 32,
+                value_len: 0
+            })
+        );
+    }
+
+    fn verify_key_range(
+        actor: &EthereumActor,
+        claim_proof: &ClaimProof,
+        range: &[(Vec<u8>, Vec<u8>)],
+        claimed_kvs: Vec<(Vec<u8>, Vec<u8>)>,
+        range_proof: Vec<Vec<u8>>,
+    ) -> Result<(), VerificationError> {
+        let claim_proof = claim_proof.clone();
+        actor.verify_key_range_claim(
+            None,
+            claim_proof.relay_header,
+            claim_proof.mmr_proof_items,
+            claim_proof.block_pos_in_mmr,
+            claim_proof.para_id,
+            claim_proof.para_header,
+            claim_proof.para_header_inclusion_proof,
+            claim_proof.para_header_merkle_root,
+            range[0].0.clone(),
+            range[range.len() - 1].0.clone(),
+            claimed_kvs,
+            range_proof,
+        )
+    }
+
+    #[test]
+    fn complete_key_range_is_accepted() {
+        let (actor, claim_proof, range_kvs, range_proof) = finalized_key_range();
+
+        assert_eq!(
+            verify_key_range(
+                &actor,
+                &claim_proof,
+                &range_kvs,
+                range_kvs.clone(),
+                range_proof
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn incomplete_key_range_is_rejected() {
+        let (actor, claim_proof, range_kvs, range_proof) = finalized_key_range();
+
+        let mut claimed_kvs = range_kvs.clone();
+        claimed_kvs.remove(2);
+        assert_eq!(
+            verify_key_range(
+                &actor,
+                &claim_proof,
+                &range_kvs,
+                claimed_kvs,
+                range_proof.clone()
+            ),
+            Err(VerificationError::KeyRangeMismatch)
+        );
+
+        // Every node of the proof is visited while iterating the range
+        for missing in 0..range_proof.len() {
+            let mut partial_proof = range_proof.clone();
+            partial_proof.remove(missing);
+            assert_eq!(
+                verify_key_range(
+                    &actor,
+                    &claim_proof,
+                    &range_kvs,
+                    range_kvs.clone(),
+                    partial_proof
+                ),
+                Err(VerificationError::StorageProofFailed)
+            );
+        }
+    }
+
+    #[test]
+    fn claim_is_verified_with_para_storage_hasher() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (_, storage_root, claimed_kv, kv_proof) =
+            generate_random_storage_and_proof::<KeccakHasher>(&mut rng, Some(10), Some(3));
+        let (actor, claim_proof) =
+            finalized_claim_with_storage((storage_root, claimed_kv.clone(), kv_proof), &mut rng);
+
+        let keccak_claim = actor.verify_claim_extract_with_para_hasher::<KeccakHasher>(
+            None,
+            claim_proof.relay_header.clone(),
+            claim_proof.mmr_proof_items.clone(),
+            claim_proof.block_pos_in_mmr,
+            claim_proof.para_id,
+            claim_proof.para_header.clone(),
+            None,
+            claim_proof.para_header_inclusion_proof.clone(),
+            claim_proof.para_header_merkle_root,
+            claim_proof.claimed_kv.clone(),
+            claim_proof.kv_proof.clone(),
+        );
+        assert_eq!(keccak_claim.unwrap().claimed_kv, claimed_kv);
+
+        // Relay hasher does not match the para storage
+        let relay_hasher_claim = actor.verify_claim_extract(
+            None,
+            claim_proof.relay_header,
+            claim_proof.mmr_proof_items,
+            claim_proof.block_pos_in_mmr,
+            claim_proof.para_id,
+            claim_proof.para_header,
+            None,
+            claim_proof.para_header_inclusion_proof,
+            claim_proof.para_header_merkle_root,
+            claim_proof.claimed_kv,
+            claim_proof.kv_proof,
+        );
+        assert_eq!(
+            relay_hasher_claim,
+            Err(VerificationError::StorageProofFailed)
+        );
+    }
+
+    // Actor finalizing relay block 5 and claim proof of relay block 2
+    fn finalized_claim() -> (EthereumActor, ClaimProof) {
+        let (blocks, signer_proofs) = chain_with_commitments();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(blocks[4].ethereum_view(), signer_proofs)
+            .unwrap();
+        (actor, blocks[1].build_claim_proof(&blocks[2], &blocks[4]))
+    }
+
+    #[test]
+    fn leaf_proof_item_verifies_like_its_hash() {
+        let (actor, claim_proof) = finalized_claim();
+        // Sibling of the claimed leaf is the leaf of relay block 1
+        let sibling = claim_proof.mmr_proof_items[0].clone();
+        assert!(matches!(sibling, MMRNode::Data(_)));
+        assert_eq!(actor.verify_claim_proof(None, &claim_proof), Ok(()));
+
+        let mut hashed = claim_proof.clone();
+        hashed.mmr_proof_items[0] = MMRNode::Hash(sibling.hash());
+        assert_eq!(actor.verify_claim_proof(None, &hashed), Ok(()));
+    }
+
+    #[test]
+    fn altered_leaf_proof_item_is_rejected() {
+        let (actor, mut claim_proof) = finalized_claim();
+        match &mut claim_proof.mmr_proof_items[0] {
+            MMRNode::Data(leaf) => leaf.para_heads_root = HashOutput::repeat_byte(1),
+            MMRNode::Hash(_) => panic!("Sibling of the claimed leaf is a leaf"),
+        }
+
+        assert_eq!(
+            actor.verify_claim_proof(None, &claim_proof),
+            Err(VerificationError::MmrProofFailed)
+        );
+    }
+
+    fn authority_set_changes(actor: &mut EthereumActor) -> Vec<ActorEvent> {
+        actor
+            .drain_events()
+            .into_iter()
+            .filter(|event| matches!(event, ActorEvent::AuthoritySetChanged { .. }))
+            .collect()
+    }
+
+    #[test]
+    fn handover_reports_joined_and_left_validators() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authority_ids(&authorities);
+        let joined = generate_beefy_pairs_from_seed(1, 1);
+        // First validator leaves, the rest stay in a different order
+        let mut next_authorities = authorities[1..].to_vec();
+        next_authorities.reverse();
+        next_authorities.extend(joined.iter().cloned());
+        let blocks = ChainBuilder::with_seed(authorities.clone(), 0)
+            .push_block_with_commitment()
+            .rotate_authorities(next_authorities)
+            .push_block_with_commitment()
+            .build();
+
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        let signer_proofs = generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]);
+        actor
+            .ingest_new_header(blocks[1].ethereum_view(), signer_proofs.clone())
+            .unwrap();
+        assert_eq!(authority_set_changes(&mut actor), vec![]);
+
+        actor
+            .ingest_new_header(blocks[2].ethereum_view(), signer_proofs)
+            .unwrap();
+        assert_eq!(
+            authority_set_changes(&mut actor),
+            vec![ActorEvent::AuthoritySetChanged {
+                old_set_id: ValidatorSetId(0),
+                new_set_id: ValidatorSetId(1),
+                diff: AuthoritySetDiff {
+                    added: authority_ids(&joined),
+                    removed: vec![ids[0].clone()],
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn handover_signed_by_incoming_set_is_rejected() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let next_authorities = generate_beefy_pairs_from_seed(5, 1);
+        let next_ids = authority_ids(&next_authorities);
+        let blocks = ChainBuilder::with_seed(authorities.clone(), 0)
+            .rotate_authorities(next_authorities.clone())
+            .push_block_with_commitment()
+            .build();
+
+        // Same handover, but signed by the set it hands over to
+        let mut view = blocks[1].ethereum_view();
+        let commitment = view.signed_commitment.unwrap().commitment;
+        view.signed_commitment = Some(generate_signed_commitment(
+            commitment.validator_set_id,
+            commitment.block_number,
+            commitment.payload,
+            &next_authorities
+                .iter()
+                .map(|(pair, _)| pair.clone())
+                .collect::<Vec<Pair>>(),
+            &[],
+            SigningScheme::Raw,
+        ));
+
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        assert_eq!(
+            actor.ingest_new_header(
+                view.clone(),
+                generate_signer_proofs(&next_ids, &[0, 1, 2, 3, 4])
+            ),
+            Err(VerificationError::AuthorityProofInvalid { index: 0 })
+        );
+        assert_eq!(
+            actor.ingest_new_header(
+                view,
+                generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4])
+            ),
+            Err(VerificationError::SignatureInvalid)
+        );
+        assert_eq!(actor.current_set_id(), ValidatorSetId(0));
+        assert_eq!(
+            actor.current_authorities(),
+            &authority_ids(&authorities)[..]
+        );
+    }
+
+    #[test]
+    fn spliced_relay_header_is_rejected() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let signer_proofs = generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]);
+        let blocks = ChainBuilder::with_seed(authorities.clone(), 0)
+            .push_block_with_commitment()
+            .push_block_with_commitment()
+            .build();
+        // Signed by the same set, but built on a different block 2
+        let other_blocks = ChainBuilder::with_seed(authorities, 1)
+            .push_block_with_commitment()
+            .push_block_with_commitment()
+            .build();
+
+        let actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        for pair in blocks.windows(2) {
+            assert_eq!(
+                actor.verify_relay_link(
+                    &pair[1].ethereum_view().relay_header,
+                    &pair[0].ethereum_view().relay_header
+                ),
+                Ok(())
+            );
+        }
+
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(blocks[1].ethereum_view(), signer_proofs.clone())
+            .unwrap();
+        assert_eq!(
+            actor.ingest_new_header(other_blocks[2].ethereum_view(), signer_proofs.clone()),
+            Err(VerificationError::RelayHeaderLinkInvalid {
+                child: BlockNumber(3),
+                parent: BlockNumber(2),
+            })
+        );
+        assert_eq!(
+            actor.ingest_new_header(blocks[2].ethereum_view(), signer_proofs),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn malformed_encoded_view_is_rejected() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let signer_proofs = generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block_with_commitment()
+            .build();
+        let encoded = blocks[1].ethereum_view().to_bytes();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+
+        for len in 0..encoded.len() {
+            assert_eq!(
+                actor.ingest_encoded(&encoded[..len], signer_proofs.clone()),
+                Err(VerificationError::DecodeFailed)
+            );
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let mut garbage = vec![0u8; rng.gen_range(0..2 * encoded.len())];
+            rng.fill(&mut garbage[..]);
+            // Garbage that happens to decode must still fail verification
+            assert!(actor
+                .ingest_encoded(&garbage, signer_proofs.clone())
+                .is_err());
+        }
+        assert_eq!(actor.last_finalized_number(), None);
+
+        assert_eq!(actor.ingest_encoded(&encoded, signer_proofs), Ok(()));
+        assert_eq!(actor.last_finalized_number(), Some(BlockNumber(2)));
+    }
+
+    #[test]
+    fn catch_up_applies_three_handovers() {
+        let sets = (0..4)
+            .map(|seed| generate_beefy_pairs_from_seed(5 + seed as usize, seed))
+            .collect::<Vec<_>>();
+        let blocks = ChainBuilder::with_seed(sets[0].clone(), 0)
+            .rotate_authorities(sets[1].clone())
+            .push_block_with_commitment()
+            .rotate_authorities(sets[2].clone())
+            .push_block_with_commitment()
+            .rotate_authorities(sets[3].clone())
+            .push_block_with_commitment()
+            .push_block_with_commitment()
+            .build();
+        // Block enacting a handover is signed by the outgoing set
+        let views = sets
+            .iter()
+            .zip(&blocks[1..])
+            .map(|(set, block)| {
+                let signers = (0..set.len() as u32).collect::<Vec<_>>();
+                (
+                    block.ethereum_view(),
+                    generate_signer_proofs(&authority_ids(set), &signers),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Skipping a handover breaks the chain of set ids
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        let skipping = vec![views[0].clone(), views[2].clone()];
+        assert_eq!(
+            actor.catch_up(skipping),
+            Err(VerificationError::ValidatorSetIdMismatch {
+                expected: ValidatorSetId(1),
+                got: ValidatorSetId(2),
+            })
+        );
+        assert_eq!(actor.last_finalized_number(), None);
+
+        assert_eq!(actor.catch_up(views), Ok(()));
+        assert_eq!(actor.current_set_id(), ValidatorSetId(3));
+        assert_eq!(actor.current_authorities(), &authority_ids(&sets[3])[..]);
+        assert_eq!(actor.last_finalized_number(), Some(BlockNumber(5)));
+    }
+
+    // View of `block` whose payload announces `new_set_id`, re-signed by `authorities`
+    fn with_new_set_id(
+        block: &BlockData,
+        new_set_id: ValidatorSetId,
+        authorities: &[(Pair, AuthorityId)],
+    ) -> EthereumView {
+        let mut view = block.ethereum_view();
+        let mut commitment = view.signed_commitment.unwrap().commitment;
+        commitment.payload.new_validator_set_id = new_set_id;
+        view.signed_commitment = Some(generate_signed_commitment(
+            commitment.validator_set_id,
+            commitment.block_number,
+            commitment.payload,
+            &authorities
+                .iter()
+                .map(|(pair, _)| pair.clone())
+                .collect::<Vec<Pair>>(),
+            &[],
+            SigningScheme::Raw,
+        ));
+        view
+    }
+
+    #[test]
+    fn set_id_increments_by_one_on_handover() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let signer_proofs = generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]);
+        let blocks = ChainBuilder::with_seed(authorities.clone(), 0)
+            .push_block_with_commitment()
+            .rotate_authorities(generate_beefy_pairs_from_seed(5, 1))
+            .push_block_with_commitment()
+            .build();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+
+        // Set id can not change without handover
+        assert_eq!(
+            actor.ingest_new_header(
+                with_new_set_id(&blocks[1], ValidatorSetId(1), &authorities),
+                signer_proofs.clone()
+            ),
+            Err(VerificationError::InvalidSetIdTransition {
+                current: ValidatorSetId(0),
+                new: ValidatorSetId(1),
+            })
+        );
+        // Nor skip ahead on handover
+        assert_eq!(
+            actor.ingest_new_header(
+                with_new_set_id(&blocks[2], ValidatorSetId(2), &authorities),
+                signer_proofs.clone()
+            ),
+            Err(VerificationError::InvalidSetIdTransition {
+                current: ValidatorSetId(0),
+                new: ValidatorSetId(2),
+            })
+        );
+        assert_eq!(actor.current_set_id(), ValidatorSetId(0));
+
+        assert_eq!(
+            actor.ingest_new_header(blocks[2].ethereum_view(), signer_proofs),
+            Ok(())
+        );
+        assert_eq!(actor.current_set_id(), ValidatorSetId(1));
+    }
+
+    #[test]
+    fn handover_is_adopted_from_finalized_leaf() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let next_authorities = generate_beefy_pairs_from_seed(5, 1);
+        let next_ids = authority_ids(&next_authorities);
+        let blocks = ChainBuilder::with_seed(authorities.clone(), 0)
+            .rotate_authorities(next_authorities.clone())
+            .push_block_with_commitment()
+            .push_block_with_commitment()
+            .push_block_with_commitment()
+            .build();
+
+        // Block 3 finalized by the outgoing set with a payload that does not announce
+        // the handover, like commitments of a running node
+        let mut view = blocks[2].ethereum_view();
+        view.signed_commitment = Some(generate_signed_commitment(
+            0,
+            3u64,
+            CommitmentPayload::new(view.beefy_mmr_root.clone(), None, ValidatorSetId(0)),
+            &authorities
+                .iter()
+                .map(|(pair, _)| pair.clone())
+                .collect::<Vec<Pair>>(),
+            &[],
+            SigningScheme::Raw,
+        ));
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                view,
+                generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+
+        // Leaf of block 2, which enacted the handover, announces the next set
+        let leaf = LeafData::<HashingAlgo>::from_relay_block(
+            &blocks[1].relay_header,
+            blocks[2].para_header_merkle_root,
+        )
+        .unwrap();
+        let claim_proof = blocks[1].build_claim_proof(&blocks[2], &blocks[2]);
+        let adopt = |actor: &mut EthereumActor, next_ids: Vec<AuthorityId>| {
+            actor.adopt_next_authority_set(
+                None,
+                claim_proof.mmr_proof_items.clone(),
+                claim_proof.block_pos_in_mmr,
+                leaf.clone(),
+                next_ids,
+            )
+        };
+
+        assert_eq!(
+            adopt(&mut actor, authority_ids(&authorities)),
+            Err(VerificationError::AuthoritySetMismatch)
+        );
+        assert_eq!(adopt(&mut actor, next_ids.clone()), Ok(()));
+        assert_eq!(actor.current_set_id(), ValidatorSetId(1));
+        assert_eq!(actor.current_authorities(), &next_ids[..]);
+
+        assert_eq!(
+            actor.ingest_new_header(
+                blocks[3].ethereum_view(),
+                generate_signer_proofs(&next_ids, &[0, 1, 2, 3, 4])
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn leaves_of_empty_mmr_are_rejected() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let blocks = ChainBuilder::with_seed(authorities.clone(), 0).build();
+
+        // Genesis finalized before any leaf is appended
+        let mut view = blocks[0].ethereum_view();
+        assert_eq!(view.beefy_mmr_root, empty_mmr_root());
+        view.relay_header
+            .digest
+            .push(mmr_root_digest_item(&view.beefy_mmr_root));
+        view.signed_commitment = Some(generate_signed_commitment(
+            0,
+            1u64,
+            CommitmentPayload::new(view.beefy_mmr_root.clone(), None, ValidatorSetId(0)),
+            &authorities
+                .iter()
+                .map(|(pair, _)| pair.clone())
+                .collect::<Vec<Pair>>(),
+            &[],
+            SigningScheme::Raw,
+        ));
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                view,
+                generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+
+        assert_eq!(
+            actor.verify_finalized_leaves(
+                None,
+                vec![],
+                vec![(MmrPosition(0), MMRNode::Hash(HashOutput::repeat_byte(1)))]
+            ),
+            Err(VerificationError::EmptyMmr)
+        );
+    }
+
+    #[test]
+    fn claim_with_paras_heads_keys_is_verified() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut blocks = vec![create_genesis_block_with_paras::<
+            HashingAlgo,
+            RealStorageKeyed,
+            LeafData,
+        >(authorities.clone(), 2000, &[2001], &mut rng)];
+        for should_generate_commitment in [false, false, true].iter() {
+            let block = create_random_child_block_with_store(
+                blocks.last().unwrap(),
+                *should_generate_commitment,
+                None,
+                &mut rng,
+            );
+            blocks.push(block);
+        }
+
+        let mut actor =
+            EthereumActor::<HashingAlgo, RealStorageKeyed>::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                blocks[3].ethereum_view(),
+                generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+        let claim_proof = blocks[1].build_claim_proof(&blocks[2], &blocks[3]);
+        assert_eq!(actor.verify_claim_proof(None, &claim_proof), Ok(()));
+
+        // Same proof does not verify for an actor expecting hashed keys
+        let mut hash_keyed_actor =
+            EthereumActor::new(authority_ids(&authorities), ValidatorSetId(0), 10, None);
+        hash_keyed_actor
+            .ingest_new_header(
+                blocks[3].ethereum_view(),
+                generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+        assert_eq!(
+            hash_keyed_actor.verify_claim_proof(None, &claim_proof),
+            Err(VerificationError::ParaInclusionFailed)
+        );
+    }
+
+    // Leaf schema other than `LeafData`, committing to parent of the relay block as well
+    #[derive(Clone, PartialEq, Debug, Encode, Decode)]
+    struct LeafWithParent {
+        block_number: u64,
+        parent_hash: HashOutput,
+        para_heads_root: HashOutput,
+    }
+
+    impl Hashable for LeafWithParent {
+        type Out = HashOutput;
+
+        fn hash_with_domain(&self, domain: &[u8]) -> Self::Out {
+            HashingAlgo::hash(&[domain, &self.encode()[..]].concat())
+        }
+    }
+
+    impl BeefyLeaf for LeafWithParent {
+        fn relay_block_number(&self) -> BlockNumber {
+            BlockNumber(self.block_number)
+        }
+
+        fn para_heads_root(&self) -> HashOutput {
+            self.para_heads_root
+        }
+
+        fn from_relay_block(header: &TestHeader, para_heads_root: HashOutput) -> Option<Self> {
+            Some(Self {
+                block_number: header.number,
+                parent_hash: header.parent_hash,
+                para_heads_root,
+            })
+        }
+    }
+
+    #[test]
+    fn claim_is_verified_with_custom_leaf() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut blocks = vec![create_genesis_block_with_paras::<
+            HashingAlgo,
+            HashKeyed,
+            LeafWithParent,
+        >(
+            authorities.clone(), DEFAULT_PARA_ID, &[], &mut rng
+        )];
+        for should_generate_commitment in [false, false, true].iter() {
+            let block = create_random_child_block_with_store(
+                blocks.last().unwrap(),
+                *should_generate_commitment,
+                None,
+                &mut rng,
+            );
+            blocks.push(block);
+        }
+
+        let mut actor = EthereumActor::<HashingAlgo, HashKeyed, LeafWithParent>::from_genesis(
+            &blocks[0], 10, None,
+        );
+        actor
+            .ingest_new_header(
+                blocks[3].ethereum_view(),
+                generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+        let claim_proof = blocks[1].build_claim_proof(&blocks[2], &blocks[3]);
+        assert_eq!(actor.verify_claim_proof(None, &claim_proof), Ok(()));
+
+        let mut claim_proof = claim_proof;
+        claim_proof.para_header_merkle_root = HashOutput::repeat_byte(1);
+        assert_eq!(
+            actor.verify_claim_proof(None, &claim_proof),
+            Err(VerificationError::MmrProofFailed)
+        );
+    }
+
+    #[test]
+    fn head_of_each_of_three_parachains_is_proven() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut blocks = vec![create_genesis_block_with_paras::<
+            HashingAlgo,
+            HashKeyed,
+            LeafData,
+        >(authorities.clone(), 2000, &[2001, 2002], &mut rng)];
+        for should_generate_commitment in [false, false, true].iter() {
+            let block = create_random_child_block_with_store(
+                blocks.last().unwrap(),
+                *should_generate_commitment,
+                None,
+                &mut rng,
+            );
+            blocks.push(block);
+        }
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                blocks[3].ethereum_view(),
+                generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+
+        // Heads of relay block 2, committed in the para heads root of its child
+        let claim_proof = blocks[1].build_claim_proof(&blocks[2], &blocks[3]);
+        let (para_heads_root, memdb) =
+            build_para_heads_trie::<HashingAlgo>(&blocks[2].encoded_para_head_data);
+        let mut heads = blocks[1].other_para_headers.clone();
+        heads.insert(2000, blocks[1].para_header.clone());
+        let verify = |para_id: ParaId, para_header: &TestHeader, proven_para_id: ParaId| {
+            let inclusion_proof = prove_para_head_inclusion(
+                &memdb,
+                para_heads_root,
+                HashKeyed::<HashingAlgo>::key(proven_para_id, para_header),
+            );
+            actor.verify_finality_only(
+                None,
+                blocks[1].relay_header.clone(),
+                claim_proof.mmr_proof_items.clone(),
+                claim_proof.block_pos_in_mmr,
+                para_id,
+                para_header.clone(),
+                inclusion_proof,
+                para_heads_root,
+                None,
+            )
+        };
+
+        assert_eq!(heads.len(), 3);
+        for (para_id, para_header) in heads.iter() {
+            assert_eq!(verify(*para_id, para_header, *para_id), Ok(()));
+        }
+        // Head of one parachain is not the head of another one
+        assert_eq!(
+            verify(2002, &heads[&2001], 2001),
+            Err(VerificationError::ParaInclusionFailed)
+        );
+    }
+
+    #[test]
+    fn adjacent_para_headers_are_linked() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block()
+            .push_block()
+            .build();
+        let actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        for pair in blocks.windows(2) {
+            assert_eq!(
+                actor.verify_para_header_link(
+                    pair[1].para_header.clone(),
+                    pair[0].para_header.clone()
+                ),
+                Ok(())
+            );
+        }
+
+        assert_eq!(
+            actor.verify_para_header_link(
+                blocks[3].para_header.clone(),
+                blocks[1].para_header.clone()
+            ),
+            Err(VerificationError::ParaHeaderLinkInvalid {
+                child: BlockNumber(4),
+                parent: BlockNumber(2),
+            })
+        );
+        assert_eq!(
+            actor.verify_para_header_link(
+                blocks[1].para_header.clone(),
+                blocks[2].para_header.clone()
+            ),
+            Err(VerificationError::ParaHeaderLinkInvalid {
+                child: BlockNumber(2),
+                parent: BlockNumber(3),
+            })
+        );
+    }
+
+    #[test]
+    fn combined_proof_of_three_leaves_is_smaller_than_separate_proofs() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let mut chain = ChainBuilder::with_seed(authorities.clone(), 0);
+        for _ in 0..6 {
+            chain = chain.push_block();
+        }
+        let blocks = chain.push_block_with_commitment().build();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                blocks[7].ethereum_view(),
+                generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+
+        let mmr = mmr_from_store::<LeafData, HashingAlgo, NoDomain, _>(
+            blocks[7].beefy_mmr_leaves,
+            &blocks[7].beefy_mmr_store,
+        );
+        let leaves = [2, 4, 6]
+            .iter()
+            .map(|&i| {
+                let leaf = LeafData::<HashingAlgo>::from_relay_block(
+                    &blocks[i].relay_header,
+                    blocks[i + 1].para_header_merkle_root,
+                )
+                .unwrap();
+                (
+                    mmr_lib::leaf_index_to_pos(blocks[i].beefy_mmr_leaves),
+                    MMRNode::Data(leaf),
+                )
+            })
+            .collect::<Vec<_>>();
+        let proof_items =
+            |positions: Vec<u64>| mmr.gen_proof(positions).unwrap().proof_items().to_vec();
+
+        let combined = proof_items(leaves.iter().map(|(pos, _)| *pos).collect());
+        let separate_len = leaves
+            .iter()
+            .map(|(pos, _)| encode_mmr_proof(&proof_items(vec![*pos])).len())
+            .sum::<usize>();
+        assert!(encode_mmr_proof(&combined).len() < separate_len);
+
+        assert_eq!(
+            actor.verify_finalized_leaves(
+                None,
+                combined,
+                leaves
+                    .into_iter()
+                    .map(|(pos, leaf)| (MmrPosition(pos), leaf))
+                    .collect()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn header_digest_must_commit_to_view_root() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let signer_proofs = generate_signer_proofs(&authority_ids(&authorities), &[0, 1, 2, 3, 4]);
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+
+        // Header committing to another root
+        let mut view = blocks[2].ethereum_view();
+        view.relay_header.digest.logs.clear();
+        view.relay_header
+            .digest
+            .push(mmr_root_digest_item(&MMRNode::<LeafData>::Hash(
+                HashOutput::repeat_byte(1),
+            )));
+        assert_eq!(
+            actor.ingest_new_header(view.clone(), signer_proofs.clone()),
+            Err(VerificationError::DigestRootMismatch)
+        );
+
+        // Header not committing to any root
+        view.relay_header.digest.logs.clear();
+        assert_eq!(
+            actor.ingest_new_header(view, signer_proofs.clone()),
+            Err(VerificationError::DigestRootMismatch)
+        );
+
+        assert_eq!(
+            actor.ingest_new_header(blocks[2].ethereum_view(), signer_proofs),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn claim_is_verified_against_earlier_finalized_root() {
+        let (blocks, signer_proofs) = chain_with_commitments();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        for i in [2, 4, 6, 8].iter() {
+            actor
+                .ingest_new_header(blocks[*i].ethereum_view(), signer_proofs.clone())
+                .unwrap();
+        }
+        // Proof of relay block 2 from the MMR finalized at block 5, not at the tip
+        let claim_proof = blocks[1].build_claim_proof(&blocks[2], &blocks[4]);
+        let verify_at = |finalized_block_number: u64| {
+            let claim_proof = claim_proof.clone();
+            actor.verify_claim_at(
+                BlockNumber(finalized_block_number),
+                claim_proof.relay_header,
+                claim_proof.mmr_proof_items,
+                claim_proof.block_pos_in_mmr,
+                claim_proof.para_id,
+                claim_proof.para_header,
+                claim_proof.para_header_inclusion_proof,
+                claim_proof.para_header_merkle_root,
+                claim_proof.claimed_kv,
+                claim_proof.kv_proof,
+            )
+        };
+
+        assert_eq!(verify_at(5), Ok(()));
+        assert!(verify_at(9).is_err());
+        assert_eq!(
+            verify_at(4),
+            Err(VerificationError::NoSuchFinalizedBlock {
+                block_number: BlockNumber(4),
+            })
+        );
+    }
+}
+
+#[cfg(all(test, feature = "trace"))]
+mod trace_tests {
+    use super::*;
+    use crate::block_generation::ChainBuilder;
+    use crate::demo::generate_beefy_pairs_from_seed;
+    use std::sync::{Arc, Mutex};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    // Records names of the spans created, in order
+    #[derive(Clone, Default)]
+    struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+    impl Subscriber for SpanNames {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut names = self.0.lock().unwrap();
+            names.push(span.metadata().name());
+            Id::from_u64(names.len() as u64)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    impl SpanNames {
+        fn take(&self) -> Vec<&'static str> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    #[test]
+    fn every_stage_has_its_span() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authorities
+            .iter()
+            .map(|(_, id)| id.clone())
+            .collect::<Vec<_>>();
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        let claim_proof = blocks[1].build_claim_proof(&blocks[2], &blocks[3]);
+
+        let span_names = SpanNames::default();
+        tracing::subscriber::with_default(span_names.clone(), || {
+            actor
+                .ingest_new_header(
+                    blocks[3].ethereum_view(),
+                    generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]),
+                )
+                .unwrap();
+            assert_eq!(span_names.take(), vec!["signature_check"]);
+
+            actor.verify_claim_proof(None, &claim_proof).unwrap();
+            assert_eq!(
+                span_names.take(),
+                vec!["mmr_verification", "para_inclusion", "storage_proof"]
+            );
+        });
     }
 }
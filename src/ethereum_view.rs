@@ -1,20 +1,28 @@
-use crate::block_generation::CommitmentPayload;
-use crate::mmr::MMRNode;
-use crate::types::{BlockNumber, HashOutput, LeafData, TestHeader};
+use crate::commitment::CommitmentPayload;
+use crate::error::VerificationError;
+use crate::ethereum_actor::{verify_para_head_proof, verify_storage_proof};
+use crate::mmr::{MMRNode, MergeStrategy};
+use crate::traits::{BeefyLeaf, ParaHeadKey};
+use crate::types::{HashOutput, HashingAlgo, LeafData, ParaId, TestHeader};
+use crate::utils::{empty_mmr_root, mmr_size_from_number_of_leaves, HashKeyed};
+use alloc::vec::Vec;
 use beefy_primitives::SignedCommitment;
-use std::vec::Vec;
+use codec::{Decode, Encode};
+use mmr_lib::util::{MemMMR, MemStore};
+use sp_core::Hasher;
 
 // Data structures that can be sent to ethereum by relayer
-pub struct EthereumView {
-    // Beefy mmr root (Technically this should be part of the block digest
-    // but for simplicity it is kept here.
-    pub(crate) beefy_mmr_root: MMRNode<LeafData>,
+// Fields that are not public are exposed read-only through getters
+#[derive(Clone, PartialEq, Encode, Decode)]
+pub struct EthereumView<L: BeefyLeaf = LeafData> {
+    // Beefy mmr root, relay header digest commits to the same root
+    pub(crate) beefy_mmr_root: MMRNode<L>,
     pub(crate) beefy_mmr_leaves: u64,
     pub(crate) relay_header: TestHeader,
     // Optional signed commitment for this block
-    pub(crate) signed_commitment:
-        Option<SignedCommitment<BlockNumber, CommitmentPayload<LeafData>>>,
+    pub(crate) signed_commitment: Option<SignedCommitment<u64, CommitmentPayload<L>>>,
 
+    pub para_id: ParaId,
     pub para_header: TestHeader,
     pub para_header_merkle_proof: Vec<Vec<u8>>,
     pub para_header_merkle_root: HashOutput,
@@ -22,3 +30,227 @@ pub struct EthereumView {
     pub(crate) chosen_kv_proof: Vec<Vec<u8>>,
     pub(crate) chosen_kv_pair: (Vec<u8>, Vec<u8>),
 }
+
+impl<L: BeefyLeaf> EthereumView<L> {
+    // SCALE encoded view, as it would be sent over the wire by relayer
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode()
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, codec::Error> {
+        Self::decode(&mut bytes)
+    }
+
+    pub fn relay_header(&self) -> &TestHeader {
+        &self.relay_header
+    }
+
+    pub fn mmr_root(&self) -> &MMRNode<L> {
+        &self.beefy_mmr_root
+    }
+
+    pub fn mmr_leaf_count(&self) -> u64 {
+        self.beefy_mmr_leaves
+    }
+
+    // Lets relayer check the view before submitting it. `mmr_store` is the
+    // beefy MMR store of the block this view was created from.
+    pub fn self_check(&self, mmr_store: &MemStore<MMRNode<L>>) -> Result<(), VerificationError> {
+        self.self_check_with_key::<HashKeyed>(mmr_store)
+    }
+
+    // Same as `self_check` for views whose para heads are keyed by `K`
+    pub fn self_check_with_key<K: ParaHeadKey>(
+        &self,
+        mmr_store: &MemStore<MMRNode<L>>,
+    ) -> Result<(), VerificationError> {
+        self.self_check_with_hasher::<HashingAlgo, K>(mmr_store)
+    }
+
+    // Same as `self_check_with_key` for views whose MMR and tries are built with `H`
+    pub fn self_check_with_hasher<H: Hasher<Out = HashOutput>, K: ParaHeadKey>(
+        &self,
+        mmr_store: &MemStore<MMRNode<L>>,
+    ) -> Result<(), VerificationError> {
+        let mem_mmr = MemMMR::<_, MergeStrategy<L, H>>::new(
+            mmr_size_from_number_of_leaves(self.beefy_mmr_leaves),
+            mmr_store.clone(),
+        );
+        let mmr_root = if self.beefy_mmr_leaves == 0 {
+            Some(empty_mmr_root())
+        } else {
+            mem_mmr.get_root().ok()
+        };
+        if mmr_root.as_ref() != Some(&self.beefy_mmr_root) {
+            return Err(VerificationError::MmrRootMismatch);
+        }
+
+        verify_para_head_proof::<H, K>(
+            self.para_id,
+            &self.para_header,
+            self.para_header_merkle_root,
+            self.para_header_merkle_proof.clone(),
+        )?;
+        verify_storage_proof::<H>(
+            self.para_header.state_root,
+            self.chosen_kv_pair.clone(),
+            self.chosen_kv_proof.clone(),
+        )
+    }
+
+    pub fn signed_commitment(&self) -> Option<&SignedCommitment<u64, CommitmentPayload<L>>> {
+        self.signed_commitment.as_ref()
+    }
+
+    pub fn chosen_kv(&self) -> &(Vec<u8>, Vec<u8>) {
+        &self.chosen_kv_pair
+    }
+
+    pub fn chosen_kv_proof(&self) -> &[Vec<u8>] {
+        &self.chosen_kv_proof
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_data::BlockData;
+    use crate::block_generation::{
+        create_genesis_block_with_paras, create_random_child_block_with_store, ChainBuilder,
+        DEFAULT_PARA_ID,
+    };
+    use crate::demo::generate_beefy_pairs_from_seed;
+    use crate::utils::RealStorageKeyed;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use sp_core::KeccakHasher;
+
+    // Genesis and three more blocks built with `H` and para heads keyed by `K`
+    fn chain<H: Hasher<Out = HashOutput>, K: ParaHeadKey>() -> Vec<BlockData<H, K, LeafData<H>>> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut blocks = vec![create_genesis_block_with_paras(
+            generate_beefy_pairs_from_seed(5, 0),
+            DEFAULT_PARA_ID,
+            &[],
+            &mut rng,
+        )];
+        for _ in 0..3 {
+            let block =
+                create_random_child_block_with_store(blocks.last().unwrap(), false, None, &mut rng);
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    #[test]
+    fn view_of_every_block_passes_self_check() {
+        for block in chain::<HashingAlgo, HashKeyed>() {
+            assert_eq!(
+                block.ethereum_view().self_check(&block.beefy_mmr_store),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn tampered_view_fails_self_check() {
+        let block = chain::<HashingAlgo, HashKeyed>().pop().unwrap();
+        let store = &block.beefy_mmr_store;
+
+        let mut view = block.ethereum_view();
+        view.beefy_mmr_leaves -= 1;
+        assert_eq!(
+            view.self_check(store),
+            Err(VerificationError::MmrRootMismatch)
+        );
+
+        let mut view = block.ethereum_view();
+        view.para_header.number += 1;
+        assert_eq!(
+            view.self_check(store),
+            Err(VerificationError::ParaInclusionFailed)
+        );
+
+        let mut view = block.ethereum_view();
+        view.chosen_kv_pair.1.push(0);
+        assert_eq!(
+            view.self_check(store),
+            Err(VerificationError::StorageProofFailed)
+        );
+    }
+
+    #[test]
+    fn self_check_uses_given_para_head_key() {
+        let block = chain::<HashingAlgo, RealStorageKeyed>().pop().unwrap();
+        let view = block.ethereum_view();
+
+        assert_eq!(
+            view.self_check_with_key::<RealStorageKeyed>(&block.beefy_mmr_store),
+            Ok(())
+        );
+        assert_eq!(
+            view.self_check(&block.beefy_mmr_store),
+            Err(VerificationError::ParaInclusionFailed)
+        );
+    }
+
+    #[test]
+    fn self_check_uses_given_hasher() {
+        let block = chain::<KeccakHasher, HashKeyed<KeccakHasher>>()
+            .pop()
+            .unwrap();
+        let view = block.ethereum_view();
+
+        assert_eq!(
+            view.self_check_with_hasher::<KeccakHasher, HashKeyed<KeccakHasher>>(
+                &block.beefy_mmr_store
+            ),
+            Ok(())
+        );
+        // MMR of several leaves is merged with the wrong hasher
+        assert_eq!(
+            view.self_check(&block.beefy_mmr_store),
+            Err(VerificationError::MmrRootMismatch)
+        );
+    }
+
+    #[test]
+    fn view_round_trips_through_bytes() {
+        let blocks = ChainBuilder::with_seed(generate_beefy_pairs_from_seed(5, 0), 0)
+            .push_block()
+            .rotate_authorities(generate_beefy_pairs_from_seed(6, 1))
+            .push_block_with_commitment()
+            .build();
+
+        // With and without signed commitment announcing a handover
+        for block in blocks.iter() {
+            let view = block.ethereum_view();
+            let decoded = EthereumView::<LeafData>::from_bytes(&view.to_bytes()).unwrap();
+            assert!(decoded == view);
+            assert_eq!(decoded.to_bytes(), view.to_bytes());
+        }
+        assert!(blocks[2].ethereum_view().signed_commitment().is_some());
+    }
+
+    #[test]
+    fn extra_payload_items_survive_view_round_trip() {
+        let blocks = ChainBuilder::with_seed(generate_beefy_pairs_from_seed(5, 0), 0)
+            .push_block_with_commitment()
+            .build();
+        let mut view = blocks[1].ethereum_view();
+        let signed_commitment = view.signed_commitment.as_mut().unwrap();
+        signed_commitment.commitment.payload = signed_commitment
+            .commitment
+            .payload
+            .clone()
+            .with_extra(*b"aa", b"first".to_vec())
+            .with_extra(*b"zz", b"last".to_vec());
+
+        let decoded = EthereumView::<LeafData>::from_bytes(&view.to_bytes()).unwrap();
+        let payload = &decoded.signed_commitment().unwrap().commitment.payload;
+        assert_eq!(payload.get_extra(*b"aa"), Some(&b"first"[..]));
+        assert_eq!(payload.get_extra(*b"zz"), Some(&b"last"[..]));
+        assert_eq!(payload.mmr_root().as_ref(), Some(decoded.mmr_root()));
+        assert!(decoded == view);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::mmr::{check_append_only, MmrStore};
+use codec::{Decode, Encode};
+use mmr_lib::Error;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::string::ToString;
+use std::vec::Vec;
+
+struct FileStoreInner<T> {
+    file: File,
+    // Every node in the file, indexed when the store is opened
+    nodes: BTreeMap<u64, T>,
+}
+
+// MMR store persisted as an append only file of SCALE encoded (position, node) records,
+// so that relayer can reopen its MMR after a restart. Clones share the same file.
+// Like every `MmrStore`, positions are written once: writing the same node again is a
+// no-op, writing a different one (e.g. a fork appended to the same store) is an error.
+#[derive(Clone)]
+pub struct FileStore<T> {
+    inner: Rc<RefCell<FileStoreInner<T>>>,
+}
+
+impl<T: Encode + Decode + Clone + PartialEq> FileStore<T> {
+    // Opens the store at `path`, creating an empty one if there is no such file
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+
+        let mut input = &bytes[..];
+        let mut nodes = BTreeMap::new();
+        while !input.is_empty() {
+            // Truncated record (e.g. crash while appending) is reported as well
+            let (pos, node) = <(u64, T)>::decode(&mut input)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            nodes.insert(pos, node);
+        }
+
+        Ok(Self {
+            inner: Rc::new(RefCell::new(FileStoreInner { file, nodes })),
+        })
+    }
+}
+
+impl<T: Encode + Decode + Clone + PartialEq> MmrStore<T> for FileStore<T> {
+    fn load_node(&self, pos: u64) -> Result<Option<T>, Error> {
+        Ok(self.inner.borrow().nodes.get(&pos).cloned())
+    }
+
+    fn store_nodes(&self, pos: u64, nodes: Vec<T>) -> Result<(), Error> {
+        let inner = &mut *self.inner.borrow_mut();
+        // Whole write is rejected before anything hits the file
+        for (i, node) in nodes.iter().enumerate() {
+            check_append_only(inner.nodes.get(&(pos + i as u64)), pos + i as u64, node)?;
+        }
+
+        let mut records = vec![];
+        for (i, node) in nodes.iter().enumerate() {
+            if !inner.nodes.contains_key(&(pos + i as u64)) {
+                (pos + i as u64, node).encode_to(&mut records);
+            }
+        }
+        // Nodes are indexed only once they are written
+        inner
+            .file
+            .write_all(&records)
+            .and_then(|_| inner.file.flush())
+            .map_err(|e| Error::StoreError(e.to_string()))?;
+        for (i, node) in nodes.into_iter().enumerate() {
+            inner.nodes.insert(pos + i as u64, node);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_generation::{
+        create_genesis_block_with_store, create_random_child_block_with_store, DEFAULT_PARA_ID,
+    };
+    use crate::demo::generate_beefy_pairs_from_seed;
+    use crate::ethereum_actor::EthereumActor;
+    use crate::mmr::{mmr_from_store, MMRNode};
+    use crate::traits::NoDomain;
+    use crate::types::{HashOutput, HashingAlgo, LeafData};
+    use crate::utils::{generate_signer_proofs, HashKeyed};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::path::PathBuf;
+
+    // Fresh file in the temp dir, removed once dropped
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("beefy_lc_{}_{}", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn conflicting_node_is_rejected() {
+        let path = TempPath::new("conflicting_node");
+        let store = FileStore::<MMRNode<LeafData>>::open(&path.0).unwrap();
+        let node = MMRNode::Hash(HashOutput::repeat_byte(1));
+        let other_node = MMRNode::Hash(HashOutput::repeat_byte(2));
+
+        store.store_nodes(0, vec![node.clone()]).unwrap();
+        assert!(store.store_nodes(0, vec![node.clone()]).is_ok());
+        assert!(matches!(
+            store.store_nodes(0, vec![other_node.clone()]),
+            Err(Error::StoreError(_))
+        ));
+        // Nothing of a rejected write is stored
+        assert!(matches!(
+            store.store_nodes(1, vec![other_node.clone(), node.clone()]),
+            Ok(())
+        ));
+        assert!(matches!(
+            store.store_nodes(2, vec![other_node.clone(), other_node.clone()]),
+            Err(Error::StoreError(_))
+        ));
+        assert_eq!(store.load_node(3).unwrap(), None);
+
+        drop(store);
+        let reopened = FileStore::<MMRNode<LeafData>>::open(&path.0).unwrap();
+        assert_eq!(reopened.load_node(0).unwrap(), Some(node));
+        assert!(matches!(
+            reopened.store_nodes(0, vec![other_node]),
+            Err(Error::StoreError(_))
+        ));
+    }
+
+    #[test]
+    fn block_is_proven_from_reopened_store() {
+        let path = TempPath::new("reopened_store");
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let authority_ids = authorities
+            .iter()
+            .map(|(_, id)| id.clone())
+            .collect::<Vec<_>>();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let mut blocks = vec![create_genesis_block_with_store::<
+            HashingAlgo,
+            HashKeyed,
+            LeafData,
+            _,
+        >(
+            FileStore::open(&path.0).unwrap(),
+            authorities,
+            DEFAULT_PARA_ID,
+            &[],
+            &mut rng,
+        )];
+        for number in 1..=6 {
+            let block = create_random_child_block_with_store(
+                blocks.last().unwrap(),
+                number == 6,
+                None,
+                &mut rng,
+            );
+            blocks.push(block);
+        }
+        let finalized_view = blocks[6].ethereum_view();
+        let finalized_leaves = blocks[6].beefy_mmr_leaves;
+        let claim_proof = blocks[2].build_claim_proof(&blocks[3], &blocks[6]);
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        drop(blocks);
+
+        let reopened = FileStore::<MMRNode<LeafData>>::open(&path.0).unwrap();
+        let mmr = mmr_from_store::<LeafData, HashingAlgo, NoDomain, _>(finalized_leaves, &reopened);
+        assert_eq!(mmr.get_root().unwrap(), finalized_view.beefy_mmr_root);
+        let mmr_proof_items = mmr
+            .gen_proof(vec![claim_proof.block_pos_in_mmr.into()])
+            .unwrap()
+            .proof_items()
+            .to_vec();
+        assert_eq!(mmr_proof_items, claim_proof.mmr_proof_items);
+
+        actor
+            .ingest_new_header(
+                finalized_view,
+                generate_signer_proofs(&authority_ids, &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+        assert_eq!(actor.verify_claim_proof(None, &claim_proof), Ok(()));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::block_data::BlockData;
+use crate::mmr::{mmr_from_store, MMRNode, MmrStore};
+use crate::traits::{BeefyLeaf, NoDomain, ParaHeadKey};
+use crate::types::HashOutput;
+use codec::Encode;
+use sp_core::Hasher;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::string::String;
+use std::vec::Vec;
+
+fn to_hex(bytes: &[u8]) -> String {
+    let digits: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("\"0x{}\"", digits)
+}
+
+fn to_hex_list<T: AsRef<[u8]>>(items: &[T]) -> String {
+    let items: Vec<String> = items.iter().map(|item| to_hex(item.as_ref())).collect();
+    format!("[{}]", items.join(", "))
+}
+
+// Test vectors for the Ethereum side verifier. Everything is SCALE encoded and hex
+// encoded into a JSON file. MMR proof is of the latest leaf (parent block) against
+// the MMR root of this block. Authorities are the set which signs after this block.
+pub fn export_fixtures<
+    H: Hasher<Out = HashOutput>,
+    K: ParaHeadKey,
+    L: BeefyLeaf,
+    S: MmrStore<MMRNode<L>>,
+>(
+    path: &Path,
+    block: &BlockData<H, K, L, S>,
+) -> io::Result<()> {
+    let view = block.ethereum_view();
+
+    let (mmr_proof_leaf_pos, mmr_proof_items) = if block.beefy_mmr_leaves == 0 {
+        (String::from("null"), Vec::new())
+    } else {
+        let leaf_pos = mmr_lib::leaf_index_to_pos(block.beefy_mmr_leaves - 1);
+        let proof_items =
+            mmr_from_store::<L, H, NoDomain, S>(block.beefy_mmr_leaves, &block.beefy_mmr_store)
+                .gen_proof(vec![leaf_pos])
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
+                .proof_items()
+                .iter()
+                .map(|item| item.encode())
+                .collect();
+        (leaf_pos.to_string(), proof_items)
+    };
+
+    let signed_commitment = match block.signed_commitment.as_ref() {
+        Some(signed_commitment) => to_hex(&signed_commitment.encode()),
+        None => String::from("null"),
+    };
+    let authorities: Vec<Vec<u8>> = block
+        .current_authority_set
+        .iter()
+        .map(|(_, id)| id.encode())
+        .collect();
+
+    let fields = vec![
+        ("block_number", block.relay_header.number.to_string()),
+        ("relay_header", to_hex(&block.relay_header.encode())),
+        ("signed_commitment", signed_commitment),
+        (
+            "authority_set_id",
+            block.current_authority_set_id.to_string(),
+        ),
+        ("authorities", to_hex_list(&authorities)),
+        ("mmr_root", to_hex(&view.mmr_root().encode())),
+        ("mmr_leaf_count", block.beefy_mmr_leaves.to_string()),
+        ("mmr_proof_leaf_pos", mmr_proof_leaf_pos),
+        ("mmr_proof_items", to_hex_list(&mmr_proof_items)),
+        ("para_id", block.para_id.to_string()),
+        ("para_header", to_hex(&block.para_header.encode())),
+        (
+            "para_heads_root",
+            to_hex(block.para_header_merkle_root.as_ref()),
+        ),
+        (
+            "para_inclusion_proof",
+            to_hex_list(&block.para_header_merkle_proof),
+        ),
+        ("claimed_key", to_hex(&block.chosen_kv_pair.0)),
+        ("claimed_value", to_hex(&block.chosen_kv_pair.1)),
+        ("storage_proof", to_hex_list(&block.chosen_kv_proof)),
+    ];
+    let body: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("  \"{}\": {}", name, value))
+        .collect();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format!("{{\n{}\n}}\n", body.join(",\n")))
+}
@@ -1,11 +1,18 @@
-use std::fmt::Debug;
+use alloc::collections::BTreeSet;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
 
 use codec::{Decode, Encode};
-use mmr_lib::Merge;
+use mmr_lib::helper::{get_peaks, parent_offset, pos_height_in_tree, sibling_offset};
+use mmr_lib::util::MemStore;
+use mmr_lib::{leaf_index_to_pos, Error, MMRStore, Merge, MerkleProof, MMR};
 use sp_core::sp_std::marker::PhantomData;
 use sp_core::Hasher;
 
-use crate::traits::Hashable;
+use crate::traits::{BeefyLeaf, Hashable, MmrDomain, NoDomain};
+use crate::utils::mmr_size_from_number_of_leaves;
 
 #[derive(Clone, PartialEq, Debug, Encode, Decode)]
 pub enum MMRNode<Leaf>
@@ -20,26 +27,1067 @@ impl<Leaf> MMRNode<Leaf>
 where
     Leaf: Hashable + Encode + Decode,
 {
-    fn hash(&self) -> Leaf::Out {
+    pub(crate) fn hash(&self) -> Leaf::Out {
+        self.hash_with_domain(&[])
+    }
+
+    fn hash_with_domain(&self, domain: &[u8]) -> Leaf::Out {
         match self {
-            Self::Data(l) => l.hash(),
+            Self::Data(l) => l.hash_with_domain(domain),
             Self::Hash(h) => *h,
         }
     }
 }
 
-pub struct MergeStrategy<L, H>(PhantomData<(L, H)>);
+impl<L: BeefyLeaf> MMRNode<L> {
+    // Readable one line description, hashes are truncated to their first 4 bytes
+    pub fn pretty(&self) -> String {
+        match self {
+            Self::Data(leaf) => format!(
+                "Data(block={}, leaf={}, para_root={})",
+                leaf.relay_block_number(),
+                short_hex(leaf.hash().as_ref()),
+                short_hex(leaf.para_heads_root().as_ref()),
+            ),
+            Self::Hash(hash) => format!("Hash({})", short_hex(hash.as_ref())),
+        }
+    }
+}
+
+fn short_hex(bytes: &[u8]) -> String {
+    let digits: String = bytes
+        .iter()
+        .take(4)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    if bytes.len() > 4 {
+        format!("0x{}…", digits)
+    } else {
+        format!("0x{}", digits)
+    }
+}
 
-impl<Leaf, H> Merge for MergeStrategy<Leaf, H>
+// Domain `D` is mixed into every leaf and node hash of the MMR
+pub struct MergeStrategy<L, H, D = NoDomain>(PhantomData<(L, H, D)>);
+
+impl<Leaf, H, D> Merge for MergeStrategy<Leaf, H, D>
 where
     Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode,
     H: Hasher,
+    D: MmrDomain,
 {
     type Item = MMRNode<Leaf>;
 
     fn merge(left: &Self::Item, right: &Self::Item) -> Self::Item {
-        let mut combined = left.hash().as_ref().to_vec();
-        combined.append(&mut right.hash().as_ref().to_vec());
-        MMRNode::Hash(H::hash(combined.as_slice()))
+        merge_nodes_with_domain::<Leaf, H>(left, right, D::TAG)
+    }
+}
+
+// Parent of two MMR nodes, hash of the concatenated hashes of left and right node
+pub fn merge_nodes<Leaf, H>(left: &MMRNode<Leaf>, right: &MMRNode<Leaf>) -> MMRNode<Leaf>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode,
+    H: Hasher,
+{
+    merge_nodes_with_domain::<Leaf, H>(left, right, &[])
+}
+
+// Same as `merge_nodes` with `domain` prepended to the hashed data (and leaves)
+pub fn merge_nodes_with_domain<Leaf, H>(
+    left: &MMRNode<Leaf>,
+    right: &MMRNode<Leaf>,
+    domain: &[u8],
+) -> MMRNode<Leaf>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode,
+    H: Hasher,
+{
+    let mut combined = domain.to_vec();
+    combined.append(&mut left.hash_with_domain(domain).as_ref().to_vec());
+    combined.append(&mut right.hash_with_domain(domain).as_ref().to_vec());
+    MMRNode::Hash(H::hash(combined.as_slice()))
+}
+
+// Backend of the beefy MMR. Like with `MemStore`, nodes are appended through a shared
+// reference, so that MMRs of different sizes (e.g. of consecutive blocks) can read
+// the same store. Stores are append only: storing the node a position already holds is
+// a no-op, storing a different one fails with `Error::StoreError` and stores nothing.
+pub trait MmrStore<T>: Clone {
+    fn load_node(&self, pos: u64) -> Result<Option<T>, Error>;
+    fn store_nodes(&self, pos: u64, nodes: Vec<T>) -> Result<(), Error>;
+}
+
+// Node at `pos` may only be written again with the same value
+pub(crate) fn check_append_only<T: PartialEq>(
+    existing: Option<&T>,
+    pos: u64,
+    node: &T,
+) -> Result<(), Error> {
+    match existing {
+        Some(existing) if existing != node => Err(Error::StoreError(format!(
+            "Position {} already holds a different node",
+            pos
+        ))),
+        _ => Ok(()),
+    }
+}
+
+impl<T: Clone + PartialEq> MmrStore<T> for MemStore<T> {
+    fn load_node(&self, pos: u64) -> Result<Option<T>, Error> {
+        (&self).get_elem(pos)
+    }
+
+    fn store_nodes(&self, pos: u64, nodes: Vec<T>) -> Result<(), Error> {
+        for (i, node) in nodes.iter().enumerate() {
+            check_append_only(
+                self.load_node(pos + i as u64)?.as_ref(),
+                pos + i as u64,
+                node,
+            )?;
+        }
+        let mut store = self;
+        store.append(pos, nodes)
+    }
+}
+
+// Clones share the same store, so that e.g. consecutive blocks of a long chain
+// do not copy the whole MMR
+impl<T, S: MmrStore<T>> MmrStore<T> for Rc<S> {
+    fn load_node(&self, pos: u64) -> Result<Option<T>, Error> {
+        (**self).load_node(pos)
+    }
+
+    fn store_nodes(&self, pos: u64, nodes: Vec<T>) -> Result<(), Error> {
+        (**self).store_nodes(pos, nodes)
+    }
+}
+
+// `mmr_lib` takes ownership of the store it works with
+pub(crate) struct StoreRef<'a, S>(pub(crate) &'a S);
+
+impl<'a, T, S: MmrStore<T>> MMRStore<T> for StoreRef<'a, S> {
+    fn get_elem(&self, pos: u64) -> mmr_lib::Result<Option<T>> {
+        self.0.load_node(pos)
+    }
+
+    fn append(&mut self, pos: u64, elems: Vec<T>) -> mmr_lib::Result<()> {
+        self.0.store_nodes(pos, elems)
+    }
+}
+
+// MMR of `leaves` leaves kept in `store`, hashed within domain `D`
+pub(crate) fn mmr_from_store<Leaf, H, D, S>(
+    leaves: u64,
+    store: &S,
+) -> MMR<MMRNode<Leaf>, MergeStrategy<Leaf, H, D>, StoreRef<'_, S>>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode + Clone + PartialEq + Debug,
+    H: Hasher,
+    D: MmrDomain,
+    S: MmrStore<MMRNode<Leaf>>,
+{
+    MMR::new(mmr_size_from_number_of_leaves(leaves), StoreRef(store))
+}
+
+// Owns the MMR store and keeps track of number of leaves, so that leaves
+// can be appended without reconstructing the MMR from its size every time
+pub struct MmrAppender<Leaf, H, S = MemStore<MMRNode<Leaf>>, D = NoDomain>
+where
+    Leaf: Hashable + Encode + Decode,
+{
+    store: S,
+    leaves: u64,
+    hasher: PhantomData<(Leaf, H, D)>,
+}
+
+impl<Leaf, H, S, D> MmrAppender<Leaf, H, S, D>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode + Clone + PartialEq + Debug,
+    H: Hasher,
+    S: MmrStore<MMRNode<Leaf>>,
+    D: MmrDomain,
+{
+    pub fn new(store: S, leaves: u64) -> Self {
+        Self {
+            store,
+            leaves,
+            hasher: PhantomData,
+        }
+    }
+
+    // Appends a leaf and returns the new root.
+    // Memory store never fails, so errors here mean a corrupted store.
+    pub fn append(&mut self, leaf: Leaf) -> MMRNode<Leaf> {
+        let mut mmr = mmr_from_store::<Leaf, H, D, S>(self.leaves, &self.store);
+        mmr.push(MMRNode::Data(leaf)).unwrap();
+        let root = mmr.get_root().unwrap();
+        mmr.commit().unwrap();
+        self.leaves += 1;
+        root
+    }
+
+    pub fn leaves(&self) -> u64 {
+        self.leaves
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+}
+
+// Canonical (SCALE) on-wire representation of MMR proof items
+pub fn encode_mmr_proof<Leaf>(proof_items: &[MMRNode<Leaf>]) -> Vec<u8>
+where
+    Leaf: Hashable + Encode + Decode,
+{
+    proof_items.encode()
+}
+
+pub fn decode_mmr_proof<Leaf>(mut bytes: &[u8]) -> Result<Vec<MMRNode<Leaf>>, codec::Error>
+where
+    Leaf: Hashable + Encode + Decode,
+{
+    Vec::<MMRNode<Leaf>>::decode(&mut bytes)
+}
+
+// Proof that MMR with fewer leaves is a prefix of the MMR with more leaves.
+// It contains peaks of the older MMR, and nodes required to climb from them
+// to the peaks of the newer MMR (or the newer peak itself if it does not
+// contain any of the older peaks), in the order verifier consumes them.
+#[derive(Clone, PartialEq, Debug, Encode, Decode)]
+pub struct AncestryProof<Leaf>
+where
+    Leaf: Hashable + Encode + Decode,
+{
+    pub prev_peaks: Vec<MMRNode<Leaf>>,
+    pub proof_items: Vec<MMRNode<Leaf>>,
+}
+
+fn bag_peaks<Leaf, H, D>(mut peaks: Vec<MMRNode<Leaf>>) -> Result<MMRNode<Leaf>, Error>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode,
+    H: Hasher,
+    D: MmrDomain,
+{
+    // Same as mmr_lib, bagging from right to left
+    while peaks.len() > 1 {
+        let right_peak = peaks.pop().unwrap();
+        let left_peak = peaks.pop().unwrap();
+        peaks.push(MergeStrategy::<Leaf, H, D>::merge(&right_peak, &left_peak));
+    }
+    peaks.pop().ok_or(Error::CorruptedProof)
+}
+
+// Merges given nodes (position, height, node) up to the peak at `peak_pos`.
+// Lowest nodes are merged first so that every known node is consumed before
+// its subtree is needed as a sibling, unknown siblings are requested from `sibling`.
+fn climb_to_peak<Leaf, H, D, F>(
+    mut nodes: Vec<(u64, u32, MMRNode<Leaf>)>,
+    peak_pos: u64,
+    mut sibling: F,
+) -> Result<MMRNode<Leaf>, Error>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode,
+    H: Hasher,
+    D: MmrDomain,
+    F: FnMut(u64) -> Result<MMRNode<Leaf>, Error>,
+{
+    loop {
+        let lowest = (0..nodes.len())
+            .min_by_key(|i| (nodes[*i].1, nodes[*i].0))
+            .ok_or(Error::CorruptedProof)?;
+        let (pos, height, node) = nodes.remove(lowest);
+        if pos == peak_pos && nodes.is_empty() {
+            return Ok(node);
+        }
+        if pos >= peak_pos {
+            return Err(Error::CorruptedProof);
+        }
+
+        let is_right_sibling = pos_height_in_tree(pos + 1) > height;
+        let (sibling_pos, parent_pos) = if is_right_sibling {
+            (pos - sibling_offset(height), pos + 1)
+        } else {
+            (pos + sibling_offset(height), pos + parent_offset(height))
+        };
+        let sibling_node = match nodes.iter().position(|(p, _, _)| *p == sibling_pos) {
+            Some(i) => nodes.remove(i).2,
+            None => sibling(sibling_pos)?,
+        };
+        let parent = if is_right_sibling {
+            MergeStrategy::<Leaf, H, D>::merge(&sibling_node, &node)
+        } else {
+            MergeStrategy::<Leaf, H, D>::merge(&node, &sibling_node)
+        };
+        nodes.push((parent_pos, height + 1, parent));
+    }
+}
+
+fn get_node<Leaf>(store: &MemStore<MMRNode<Leaf>>, pos: u64) -> Result<MMRNode<Leaf>, Error>
+where
+    Leaf: Hashable + Encode + Decode + Clone,
+{
+    store.get_elem(pos)?.ok_or(Error::InconsistentStore)
+}
+
+pub fn generate_mmr_ancestry_proof<Leaf, H>(
+    prev_leaves: u64,
+    leaves: u64,
+    store: &MemStore<MMRNode<Leaf>>,
+) -> Result<AncestryProof<Leaf>, Error>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode + Clone,
+    H: Hasher,
+{
+    generate_mmr_ancestry_proof_with_domain::<Leaf, H, NoDomain>(prev_leaves, leaves, store)
+}
+
+// Same as `generate_mmr_ancestry_proof` for MMR hashed within domain `D`
+pub fn generate_mmr_ancestry_proof_with_domain<Leaf, H, D>(
+    prev_leaves: u64,
+    leaves: u64,
+    store: &MemStore<MMRNode<Leaf>>,
+) -> Result<AncestryProof<Leaf>, Error>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode + Clone,
+    H: Hasher,
+    D: MmrDomain,
+{
+    if prev_leaves == 0 || prev_leaves > leaves {
+        return Err(Error::GenProofForInvalidLeaves);
+    }
+
+    let prev_peak_positions = get_peaks(mmr_size_from_number_of_leaves(prev_leaves));
+    let prev_peaks = prev_peak_positions
+        .iter()
+        .map(|pos| get_node(store, *pos))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut prev_nodes = prev_peak_positions
+        .into_iter()
+        .zip(prev_peaks.clone().into_iter())
+        .peekable();
+    let mut proof_items = vec![];
+    for peak_pos in get_peaks(mmr_size_from_number_of_leaves(leaves)) {
+        let mut nodes = vec![];
+        while let Some(pos) = prev_nodes.peek().map(|(pos, _)| *pos) {
+            if pos > peak_pos {
+                break;
+            }
+            let (pos, node) = prev_nodes.next().unwrap();
+            nodes.push((pos, pos_height_in_tree(pos), node));
+        }
+
+        if nodes.is_empty() {
+            proof_items.push(get_node(store, peak_pos)?);
+        } else {
+            climb_to_peak::<Leaf, H, D, _>(nodes, peak_pos, |pos| {
+                let node = get_node(store, pos)?;
+                proof_items.push(node.clone());
+                Ok(node)
+            })?;
+        }
+    }
+
+    Ok(AncestryProof {
+        prev_peaks,
+        proof_items,
+    })
+}
+
+pub fn verify_mmr_ancestry_proof<Leaf, H>(
+    prev_root: MMRNode<Leaf>,
+    prev_leaves: u64,
+    root: MMRNode<Leaf>,
+    leaves: u64,
+    ancestry_proof: AncestryProof<Leaf>,
+) -> Result<bool, Error>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode + Clone + PartialEq,
+    H: Hasher,
+{
+    verify_mmr_ancestry_proof_with_domain::<Leaf, H, NoDomain>(
+        prev_root,
+        prev_leaves,
+        root,
+        leaves,
+        ancestry_proof,
+    )
+}
+
+// Same as `verify_mmr_ancestry_proof` for MMRs hashed within domain `D`
+pub fn verify_mmr_ancestry_proof_with_domain<Leaf, H, D>(
+    prev_root: MMRNode<Leaf>,
+    prev_leaves: u64,
+    root: MMRNode<Leaf>,
+    leaves: u64,
+    ancestry_proof: AncestryProof<Leaf>,
+) -> Result<bool, Error>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode + Clone + PartialEq,
+    H: Hasher,
+    D: MmrDomain,
+{
+    if prev_leaves == 0 || prev_leaves > leaves {
+        return Ok(false);
+    }
+
+    let prev_peak_positions = get_peaks(mmr_size_from_number_of_leaves(prev_leaves));
+    if prev_peak_positions.len() != ancestry_proof.prev_peaks.len() {
+        return Err(Error::CorruptedProof);
+    }
+    if bag_peaks::<Leaf, H, D>(ancestry_proof.prev_peaks.clone())? != prev_root {
+        return Ok(false);
+    }
+
+    let mut prev_nodes = prev_peak_positions
+        .into_iter()
+        .zip(ancestry_proof.prev_peaks.into_iter())
+        .peekable();
+    let mut proof_items = ancestry_proof.proof_items.into_iter();
+    let mut peaks = vec![];
+    for peak_pos in get_peaks(mmr_size_from_number_of_leaves(leaves)) {
+        let mut nodes = vec![];
+        while let Some(pos) = prev_nodes.peek().map(|(pos, _)| *pos) {
+            if pos > peak_pos {
+                break;
+            }
+            let (pos, node) = prev_nodes.next().unwrap();
+            nodes.push((pos, pos_height_in_tree(pos), node));
+        }
+
+        let peak = if nodes.is_empty() {
+            proof_items.next().ok_or(Error::CorruptedProof)?
+        } else {
+            climb_to_peak::<Leaf, H, D, _>(nodes, peak_pos, |_| {
+                proof_items.next().ok_or(Error::CorruptedProof)
+            })?
+        };
+        peaks.push(peak);
+    }
+
+    if proof_items.next().is_some() || prev_nodes.next().is_some() {
+        return Err(Error::CorruptedProof);
+    }
+
+    Ok(bag_peaks::<Leaf, H, D>(peaks)? == root)
+}
+
+// Copies only the nodes needed to generate proofs for leaves at or after
+// `keep_from_leaf` (and to keep appending), which are the peaks and the
+// siblings on the path of every kept leaf to its peak.
+pub fn prune_store<Leaf>(
+    store: &MemStore<MMRNode<Leaf>>,
+    leaves: u64,
+    keep_from_leaf: u64,
+) -> Result<MemStore<MMRNode<Leaf>>, Error>
+where
+    Leaf: Hashable + Encode + Decode + Clone,
+{
+    let peaks = get_peaks(mmr_size_from_number_of_leaves(leaves));
+    let mut positions = peaks.iter().cloned().collect::<BTreeSet<u64>>();
+    for leaf_index in keep_from_leaf..leaves {
+        let mut pos = leaf_index_to_pos(leaf_index);
+        let mut height = 0;
+        positions.insert(pos);
+        while !peaks.contains(&pos) {
+            if pos_height_in_tree(pos + 1) > height {
+                positions.insert(pos - sibling_offset(height));
+                pos += 1;
+            } else {
+                positions.insert(pos + sibling_offset(height));
+                pos += parent_offset(height);
+            }
+            height += 1;
+        }
+    }
+
+    let pruned_store = MemStore::default();
+    {
+        let mut pruned = &pruned_store;
+        for pos in positions {
+            pruned.append(pos, vec![get_node(store, pos)?])?;
+        }
+    }
+    Ok(pruned_store)
+}
+
+// MMR known only by its peaks, e.g. a relayer bootstrapping from a snapshot which
+// does not persist inner nodes. Peaks are enough to compute the root, to verify
+// proofs against it and to keep appending leaves (new peaks only merge old ones).
+// Proofs can not be generated, for leaves before the snapshot they need inner
+// nodes which are gone, so they have to be supplied by someone keeping a full store.
+pub struct MmrFromPeaks<Leaf, H, D = NoDomain>
+where
+    Leaf: Hashable + Encode + Decode,
+{
+    // Ordered by position, as returned by `get_peaks`
+    peaks: Vec<MMRNode<Leaf>>,
+    leaves: u64,
+    hasher: PhantomData<(H, D)>,
+}
+
+impl<Leaf, H, D> MmrFromPeaks<Leaf, H, D>
+where
+    Leaf: Hashable<Out = <H as Hasher>::Out> + Encode + Decode + Clone + PartialEq + Debug,
+    H: Hasher,
+    D: MmrDomain,
+{
+    // Fails if number of peaks does not match MMR of `leaves` leaves
+    pub fn new(peaks: Vec<MMRNode<Leaf>>, leaves: u64) -> Result<Self, Error> {
+        if get_peaks(mmr_size_from_number_of_leaves(leaves)).len() != peaks.len() {
+            return Err(Error::InconsistentStore);
+        }
+        Ok(Self {
+            peaks,
+            leaves,
+            hasher: PhantomData,
+        })
+    }
+
+    // Takes the peaks out of a full store, which can be dropped afterwards
+    pub fn from_store<S: MmrStore<MMRNode<Leaf>>>(store: &S, leaves: u64) -> Result<Self, Error> {
+        let peaks = get_peaks(mmr_size_from_number_of_leaves(leaves))
+            .into_iter()
+            .map(|pos| store.load_node(pos)?.ok_or(Error::InconsistentStore))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(peaks, leaves)
+    }
+
+    pub fn peaks(&self) -> &[MMRNode<Leaf>] {
+        &self.peaks
+    }
+
+    pub fn leaves(&self) -> u64 {
+        self.leaves
+    }
+
+    // Same root as the full MMR, peaks are bagged the way mmr_lib does
+    pub fn root(&self) -> Result<MMRNode<Leaf>, Error> {
+        bag_peaks::<Leaf, H, D>(self.peaks.clone())
+    }
+
+    // Verifies a proof generated from the full store of an MMR with the same leaves
+    pub fn verify(
+        &self,
+        proof_items: Vec<MMRNode<Leaf>>,
+        leaves: Vec<(u64, MMRNode<Leaf>)>,
+    ) -> Result<bool, Error> {
+        MerkleProof::<_, MergeStrategy<Leaf, H, D>>::new(
+            mmr_size_from_number_of_leaves(self.leaves),
+            proof_items,
+        )
+        .verify(self.root()?, leaves)
+    }
+
+    // Appends a leaf, merging it with every peak of the same height
+    pub fn append(&mut self, leaf: Leaf) {
+        let mut heights = get_peaks(mmr_size_from_number_of_leaves(self.leaves))
+            .into_iter()
+            .map(pos_height_in_tree)
+            .collect::<Vec<u32>>();
+        let mut node = MMRNode::Data(leaf);
+        let mut height = 0;
+        while heights.last() == Some(&height) {
+            heights.pop();
+            let left_peak = self.peaks.pop().unwrap();
+            node = MergeStrategy::<Leaf, H, D>::merge(&left_peak, &node);
+            height += 1;
+        }
+        self.peaks.push(node);
+        self.leaves += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{HashOutput, HashingAlgo, LeafData};
+    use crate::utils::compute_mmr_root;
+    use proptest::prelude::*;
+
+    fn leaf(block_number: u64, seed: u8) -> LeafData {
+        LeafData {
+            block_number,
+            block_hash: HashOutput::repeat_byte(seed),
+            para_heads_root: HashOutput::repeat_byte(seed.wrapping_add(1)),
+            next_authority_set_id: 0,
+            next_authority_set_len: 5,
+            next_authority_set_root: HashOutput::repeat_byte(seed.wrapping_add(2)),
+            hasher: PhantomData,
+        }
+    }
+
+    fn leaves(seeds: &[u8]) -> Vec<LeafData> {
+        seeds
+            .iter()
+            .enumerate()
+            .map(|(i, seed)| leaf(i as u64, *seed))
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn same_leaves_give_same_root(seeds in prop::collection::vec(any::<u8>(), 1..64)) {
+            prop_assert_eq!(compute_mmr_root(&leaves(&seeds)), compute_mmr_root(&leaves(&seeds)));
+        }
+
+        #[test]
+        fn changed_leaf_changes_root(
+            seeds in prop::collection::vec(any::<u8>(), 1..64),
+            index in any::<prop::sample::Index>(),
+        ) {
+            let original = leaves(&seeds);
+            let mut changed = original.clone();
+            let i = index.index(seeds.len());
+            changed[i].block_hash = HashOutput::repeat_byte(!seeds[i]);
+            prop_assert_ne!(compute_mmr_root(&original), compute_mmr_root(&changed));
+        }
+
+        #[test]
+        fn garbage_proof_is_rejected_without_panic(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+            if let Ok(proof_items) = decode_mmr_proof::<LeafData>(&bytes) {
+                let encoded = encode_mmr_proof(&proof_items);
+                prop_assert_eq!(decode_mmr_proof::<LeafData>(&encoded).unwrap(), proof_items);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_or_oversized_proof_is_rejected() {
+        let encoded = encode_mmr_proof(&[
+            MMRNode::Data(leaf(42, 7)),
+            MMRNode::Hash(HashOutput::repeat_byte(9)),
+        ]);
+        for len in 0..encoded.len() {
+            assert!(decode_mmr_proof::<LeafData>(&encoded[..len]).is_err());
+        }
+
+        // Length prefix promising far more items than there are bytes
+        let oversized = codec::Compact(u32::MAX).encode();
+        assert!(decode_mmr_proof::<LeafData>(&oversized).is_err());
+    }
+
+    #[test]
+    fn data_node_round_trips() {
+        let node = MMRNode::Data(leaf(42, 7));
+        let encoded = node.encode();
+        // Discriminant of `Data` comes first
+        assert_eq!(encoded[0], 0);
+        assert_eq!(
+            MMRNode::<LeafData>::decode(&mut &encoded[..]).unwrap(),
+            node
+        );
+    }
+
+    #[test]
+    fn hash_node_round_trips() {
+        let node = MMRNode::<LeafData>::Hash(HashOutput::repeat_byte(9));
+        let encoded = node.encode();
+        assert_eq!(encoded[0], 1);
+        assert_eq!(
+            MMRNode::<LeafData>::decode(&mut &encoded[..]).unwrap(),
+            node
+        );
+    }
+
+    #[test]
+    fn truncated_node_fails_to_decode() {
+        for node in [
+            MMRNode::Data(leaf(42, 7)),
+            MMRNode::Hash(HashOutput::repeat_byte(9)),
+        ]
+        .iter()
+        {
+            let encoded = node.encode();
+            for len in 0..encoded.len() {
+                assert!(MMRNode::<LeafData>::decode(&mut &encoded[..len]).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn proof_of_ten_leaf_mmr_round_trips() {
+        let store = MemStore::default();
+        let mut mmr = MMR::<_, MergeStrategy<LeafData, HashingAlgo>, _>::new(0, &store);
+        for seed in 0..10 {
+            mmr.push(MMRNode::Data(leaf(seed as u64, seed))).unwrap();
+        }
+        let root = mmr.get_root().unwrap();
+        let pos = leaf_index_to_pos(6);
+        let proof_items = mmr.gen_proof(vec![pos]).unwrap().proof_items().to_vec();
+
+        let decoded = decode_mmr_proof::<LeafData>(&encode_mmr_proof(&proof_items)).unwrap();
+        assert_eq!(decoded, proof_items);
+        let proof =
+            MerkleProof::<_, MergeStrategy<LeafData, HashingAlgo>>::new(mmr.mmr_size(), decoded);
+        assert!(proof
+            .verify(root, vec![(pos, MMRNode::Data(leaf(6, 6)))])
+            .unwrap());
+    }
+
+    #[test]
+    fn mmr_size_matches_mmr_lib() {
+        let store = MemStore::default();
+        let mut mmr = MMR::<_, MergeStrategy<LeafData, HashingAlgo>, _>::new(0, &store);
+        for leaves in 0..1000 {
+            assert_eq!(mmr_size_from_number_of_leaves(leaves), mmr.mmr_size());
+            mmr.push(MMRNode::Data(leaf(leaves, 0))).unwrap();
+        }
+    }
+
+    struct DomainA;
+    struct DomainB;
+
+    impl MmrDomain for DomainA {
+        const TAG: &'static [u8] = b"domain a";
+    }
+
+    impl MmrDomain for DomainB {
+        const TAG: &'static [u8] = b"domain b";
+    }
+
+    fn mmr_with_domain<D: MmrDomain>(
+        leaves: &[LeafData],
+    ) -> (MMRNode<LeafData>, MemStore<MMRNode<LeafData>>) {
+        let mut appender = MmrAppender::<LeafData, HashingAlgo, _, D>::new(MemStore::default(), 0);
+        let root = leaves
+            .iter()
+            .map(|leaf| appender.append(leaf.clone()))
+            .last()
+            .unwrap();
+        (root, appender.into_store())
+    }
+
+    #[test]
+    fn same_leaves_give_different_roots_under_different_domains() {
+        let leaves = leaves(&[1, 2, 3, 4, 5]);
+        let (root_a, store_a) = mmr_with_domain::<DomainA>(&leaves);
+        let (root_b, _) = mmr_with_domain::<DomainB>(&leaves);
+        assert_ne!(root_a, root_b);
+        assert_ne!(root_a, compute_mmr_root(&leaves));
+        let from_peaks =
+            MmrFromPeaks::<LeafData, HashingAlgo, DomainA>::from_store(&store_a, 5).unwrap();
+        assert_eq!(from_peaks.root().unwrap(), root_a);
+
+        // Ancestry is proven only within the domain the MMRs are hashed in
+        let (prev_root_a, _) = mmr_with_domain::<DomainA>(&leaves[..3]);
+        let proof = generate_mmr_ancestry_proof_with_domain::<LeafData, HashingAlgo, DomainA>(
+            3, 5, &store_a,
+        )
+        .unwrap();
+        assert!(
+            verify_mmr_ancestry_proof_with_domain::<_, HashingAlgo, DomainA>(
+                prev_root_a.clone(),
+                3,
+                root_a.clone(),
+                5,
+                proof.clone(),
+            )
+            .unwrap()
+        );
+        assert!(
+            !verify_mmr_ancestry_proof_with_domain::<_, HashingAlgo, DomainB>(
+                prev_root_a,
+                3,
+                root_a,
+                5,
+                proof,
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn mem_store_rejects_conflicting_node() {
+        let store = MemStore::default();
+        let node = MMRNode::Data(leaf(1, 1));
+
+        store.store_nodes(0, vec![node.clone()]).unwrap();
+        assert!(store.store_nodes(0, vec![node.clone()]).is_ok());
+        assert!(matches!(
+            store.store_nodes(0, vec![MMRNode::Data(leaf(1, 2))]),
+            Err(Error::StoreError(_))
+        ));
+        assert_eq!(store.load_node(0).unwrap(), Some(node));
+    }
+
+    #[test]
+    fn appender_root_matches_computed_root() {
+        let seeds = (0..64).collect::<Vec<u8>>();
+        let mut appender = MmrAppender::<LeafData, HashingAlgo>::new(MemStore::default(), 0);
+        for (number, leaf) in leaves(&seeds).into_iter().enumerate() {
+            let root = appender.append(leaf);
+            assert_eq!(appender.leaves(), number as u64 + 1);
+            assert_eq!(root, compute_mmr_root(&leaves(&seeds[..=number])));
+        }
+    }
+
+    #[test]
+    fn pretty_node_shows_truncated_hashes() {
+        let hash = MMRNode::<LeafData>::Hash(HashOutput::repeat_byte(0xab));
+        assert_eq!(hash.pretty(), "Hash(0xabababab…)");
+
+        let data = MMRNode::Data(leaf(7, 8)).pretty();
+        assert!(data.starts_with("Data(block=7, leaf=0x"));
+        assert!(data.ends_with(", para_root=0x09090909…)"));
+    }
+
+    #[test]
+    fn mmr_from_peaks_continues_full_mmr() {
+        let seeds = (0..100).collect::<Vec<u8>>();
+        let all_leaves = leaves(&seeds);
+        let store = Rc::new(MemStore::<MMRNode<LeafData>>::default());
+        let mut appender = MmrAppender::<LeafData, HashingAlgo, _>::new(store.clone(), 0);
+        let mut full_roots = vec![];
+        for leaf in all_leaves.iter() {
+            full_roots.push(appender.append(leaf.clone()));
+        }
+        let full_root = |leaves: usize| full_roots[leaves - 1].clone();
+
+        // Loaded at every size
+        for leaves in 1..=all_leaves.len() {
+            let from_peaks =
+                MmrFromPeaks::<LeafData, HashingAlgo>::from_store(&store, leaves as u64).unwrap();
+            assert_eq!(from_peaks.root().unwrap(), full_root(leaves));
+        }
+
+        // Grown from a single leaf to every size
+        let mut from_peaks = MmrFromPeaks::<LeafData, HashingAlgo>::from_store(&store, 1).unwrap();
+        for (number, leaf) in all_leaves.iter().enumerate().skip(1) {
+            from_peaks.append(leaf.clone());
+            assert_eq!(from_peaks.leaves(), number as u64 + 1);
+            assert_eq!(from_peaks.root().unwrap(), full_root(number + 1));
+        }
+        assert_eq!(full_root(100), compute_mmr_root(&all_leaves));
+
+        // Proofs are generated from the full store
+        for leaf_index in [0, 3, 63, 99].iter() {
+            let pos = leaf_index_to_pos(*leaf_index);
+            let proof_items = mmr_from_store::<LeafData, HashingAlgo, NoDomain, _>(100, &store)
+                .gen_proof(vec![pos])
+                .unwrap()
+                .proof_items()
+                .to_vec();
+            assert!(from_peaks
+                .verify(
+                    proof_items,
+                    vec![(pos, MMRNode::Data(all_leaves[*leaf_index as usize].clone()))]
+                )
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn mmr_from_peaks_rejects_inconsistent_input() {
+        let all_leaves = leaves(&[1, 2, 3]);
+        // MMR of 3 leaves has 2 peaks
+        assert!(matches!(
+            MmrFromPeaks::<LeafData, HashingAlgo>::new(vec![], 3),
+            Err(Error::InconsistentStore)
+        ));
+
+        let store = MemStore::default();
+        let mut appender = MmrAppender::<LeafData, HashingAlgo>::new(store, 0);
+        for leaf in all_leaves.iter() {
+            appender.append(leaf.clone());
+        }
+        let store = appender.into_store();
+        let from_peaks = MmrFromPeaks::<LeafData, HashingAlgo>::from_store(&store, 3).unwrap();
+        let pos = leaf_index_to_pos(0);
+        let proof_items = mmr_from_store::<LeafData, HashingAlgo, NoDomain, _>(3, &store)
+            .gen_proof(vec![pos])
+            .unwrap()
+            .proof_items()
+            .to_vec();
+        assert!(!from_peaks
+            .verify(
+                proof_items,
+                vec![(pos, MMRNode::Data(all_leaves[1].clone()))]
+            )
+            .unwrap());
+        // Store does not hold peaks of a larger MMR
+        assert!(matches!(
+            MmrFromPeaks::<LeafData, HashingAlgo>::from_store(&store, 4),
+            Err(Error::InconsistentStore)
+        ));
+    }
+
+    fn mem_mmr(leaves: &[LeafData]) -> MemStore<MMRNode<LeafData>> {
+        let mut appender = MmrAppender::<LeafData, HashingAlgo>::new(MemStore::default(), 0);
+        for leaf in leaves.iter() {
+            appender.append(leaf.clone());
+        }
+        appender.into_store()
+    }
+
+    fn verify_ancestry(
+        prev_leaves: &[LeafData],
+        leaves: &[LeafData],
+        proof: AncestryProof<LeafData>,
+    ) -> Result<bool, Error> {
+        verify_mmr_ancestry_proof::<_, HashingAlgo>(
+            compute_mmr_root(prev_leaves),
+            prev_leaves.len() as u64,
+            compute_mmr_root(leaves),
+            leaves.len() as u64,
+            proof,
+        )
+    }
+
+    #[test]
+    fn ancestry_is_proven_across_mmr_sizes() {
+        let all_leaves = leaves(&(0..33).collect::<Vec<u8>>());
+        let store = mem_mmr(&all_leaves);
+        for count in 1..=all_leaves.len() {
+            for prev_count in 1..=count {
+                let proof = generate_mmr_ancestry_proof::<_, HashingAlgo>(
+                    prev_count as u64,
+                    count as u64,
+                    &store,
+                )
+                .unwrap();
+                assert!(
+                    verify_ancestry(&all_leaves[..prev_count], &all_leaves[..count], proof)
+                        .unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_ancestry_proof_is_rejected() {
+        let all_leaves = leaves(&(0..11).collect::<Vec<u8>>());
+        let store = mem_mmr(&all_leaves);
+        let proof = generate_mmr_ancestry_proof::<_, HashingAlgo>(3, 11, &store).unwrap();
+        assert!(!proof.proof_items.is_empty());
+
+        for index in 0..proof.proof_items.len() {
+            let mut tampered = proof.clone();
+            tampered.proof_items[index] = MMRNode::Hash(HashOutput::repeat_byte(0xff));
+            assert!(!verify_ancestry(&all_leaves[..3], &all_leaves, tampered).unwrap_or(false));
+        }
+        for index in 0..proof.prev_peaks.len() {
+            let mut tampered = proof.clone();
+            tampered.prev_peaks[index] = MMRNode::Hash(HashOutput::repeat_byte(0xff));
+            assert!(!verify_ancestry(&all_leaves[..3], &all_leaves, tampered).unwrap_or(false));
+        }
+
+        let mut truncated = proof.clone();
+        truncated.proof_items.pop();
+        assert!(matches!(
+            verify_ancestry(&all_leaves[..3], &all_leaves, truncated),
+            Err(Error::CorruptedProof)
+        ));
+        let mut extended = proof;
+        extended
+            .proof_items
+            .push(MMRNode::Hash(HashOutput::repeat_byte(0xff)));
+        assert!(matches!(
+            verify_ancestry(&all_leaves[..3], &all_leaves, extended),
+            Err(Error::CorruptedProof)
+        ));
+    }
+
+    #[test]
+    fn forked_mmr_is_not_proven_to_extend_another() {
+        let seeds = (0..11).collect::<Vec<u8>>();
+        let main = leaves(&seeds);
+        // Fork shares the first 4 leaves only
+        let mut fork_seeds = seeds.clone();
+        fork_seeds[4] = 100;
+        let fork = leaves(&fork_seeds);
+        let fork_store = mem_mmr(&fork);
+
+        // Fork extends its own prefix, but not the prefix of the main MMR
+        let proof = generate_mmr_ancestry_proof::<_, HashingAlgo>(6, 11, &fork_store).unwrap();
+        assert!(verify_ancestry(&fork[..6], &fork, proof.clone()).unwrap());
+        assert!(!verify_ancestry(&main[..6], &fork, proof.clone()).unwrap());
+        assert!(!verify_ancestry(&main[..6], &main, proof).unwrap());
+
+        // Shared prefix is extended by both
+        let proof = generate_mmr_ancestry_proof::<_, HashingAlgo>(4, 11, &fork_store).unwrap();
+        assert!(verify_ancestry(&main[..4], &fork, proof).unwrap());
+    }
+
+    #[test]
+    fn nodes_climb_to_their_peak() {
+        let all_leaves = leaves(&[1, 2, 3, 4]);
+        let store = mem_mmr(&all_leaves);
+        let node = |pos| get_node(&store, pos).unwrap();
+        let climb = |nodes: Vec<(u64, u32, MMRNode<LeafData>)>, peak_pos| {
+            climb_to_peak::<_, HashingAlgo, NoDomain, _>(nodes, peak_pos, |pos| Ok(node(pos)))
+        };
+        // Peak of 4 leaves is at position 6
+        assert_eq!(climb(vec![(0, 0, node(0))], 6).unwrap(), node(6));
+        assert_eq!(climb(vec![(3, 0, node(3))], 6).unwrap(), node(6));
+        assert_eq!(
+            climb(vec![(2, 1, node(2)), (4, 0, node(4))], 6).unwrap(),
+            node(6)
+        );
+        // Known siblings are used instead of the store
+        let forged = MMRNode::Hash(HashOutput::repeat_byte(0xff));
+        assert_ne!(
+            climb(vec![(0, 0, node(0)), (1, 0, forged)], 6).unwrap(),
+            node(6)
+        );
+        // Nodes can not climb past the peak
+        assert!(matches!(
+            climb(vec![(7, 0, node(0))], 6),
+            Err(Error::CorruptedProof)
+        ));
+        assert!(matches!(climb(vec![], 6), Err(Error::CorruptedProof)));
+    }
+
+    #[test]
+    fn pruned_store_keeps_proofs_of_retained_leaves() {
+        let all_leaves = (0..1001u64)
+            .map(|number| leaf(number, number as u8))
+            .collect::<Vec<_>>();
+        let store = mem_mmr(&all_leaves[..1000]);
+        let root = compute_mmr_root(&all_leaves[..1000]);
+        let pruned = prune_store(&store, 1000, 990).unwrap();
+
+        let node_count = |store: &MemStore<MMRNode<LeafData>>| {
+            (0..mmr_size_from_number_of_leaves(1000))
+                .filter(|pos| store.load_node(*pos).unwrap().is_some())
+                .count()
+        };
+        assert_eq!(
+            node_count(&store) as u64,
+            mmr_size_from_number_of_leaves(1000)
+        );
+        assert!(node_count(&pruned) * 10 < node_count(&store));
+
+        let proof = |store: &MemStore<MMRNode<LeafData>>, leaf_index: u64| {
+            mmr_from_store::<LeafData, HashingAlgo, NoDomain, _>(1000, store)
+                .gen_proof(vec![leaf_index_to_pos(leaf_index)])
+        };
+        for leaf_index in 990..1000 {
+            let leaf_proof = proof(&pruned, leaf_index).unwrap();
+            assert!(leaf_proof
+                .verify(
+                    root.clone(),
+                    vec![(
+                        leaf_index_to_pos(leaf_index),
+                        MMRNode::Data(all_leaves[leaf_index as usize].clone())
+                    )]
+                )
+                .unwrap());
+        }
+        // Inner nodes on the path of older leaves are gone
+        assert!(proof(&store, 0).is_ok());
+        assert!(proof(&pruned, 0).is_err());
+
+        // Peaks are kept, so that leaves can still be appended
+        let mut appender = MmrAppender::<LeafData, HashingAlgo>::new(pruned, 1000);
+        assert_eq!(
+            appender.append(all_leaves[1000].clone()),
+            compute_mmr_root(&all_leaves)
+        );
     }
 }
@@ -0,0 +1,130 @@
+use alloc::vec::Vec;
+use codec::{Decode, Encode};
+
+// Para header inclusion proof and storage proof submitted together, with every
+// trie node stored once. Each proof is a list of indices into the shared nodes.
+#[derive(Clone, PartialEq, Debug, Encode, Decode)]
+pub struct DeduplicatedProofBundle {
+    pub nodes: Vec<Vec<u8>>,
+    pub para_inclusion_indices: Vec<u32>,
+    pub kv_indices: Vec<u32>,
+}
+
+impl DeduplicatedProofBundle {
+    pub fn new(para_inclusion_proof: Vec<Vec<u8>>, kv_proof: Vec<Vec<u8>>) -> Self {
+        let mut nodes = vec![];
+        let para_inclusion_indices = Self::index_nodes(&mut nodes, para_inclusion_proof);
+        let kv_indices = Self::index_nodes(&mut nodes, kv_proof);
+        Self {
+            nodes,
+            para_inclusion_indices,
+            kv_indices,
+        }
+    }
+
+    fn index_nodes(nodes: &mut Vec<Vec<u8>>, proof: Vec<Vec<u8>>) -> Vec<u32> {
+        proof
+            .into_iter()
+            .map(|node| match nodes.iter().position(|n| *n == node) {
+                Some(index) => index as u32,
+                None => {
+                    nodes.push(node);
+                    (nodes.len() - 1) as u32
+                }
+            })
+            .collect()
+    }
+
+    // None if bundle refers to a node it does not contain
+    fn reconstitute(&self, indices: &[u32]) -> Option<Vec<Vec<u8>>> {
+        indices
+            .iter()
+            .map(|index| self.nodes.get(*index as usize).cloned())
+            .collect()
+    }
+
+    pub fn para_inclusion_proof(&self) -> Option<Vec<Vec<u8>>> {
+        self.reconstitute(&self.para_inclusion_indices)
+    }
+
+    pub fn kv_proof(&self) -> Option<Vec<Vec<u8>>> {
+        self.reconstitute(&self.kv_indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_generation::ChainBuilder;
+    use crate::demo::generate_beefy_pairs_from_seed;
+    use crate::ethereum_actor::EthereumActor;
+    use crate::utils::generate_signer_proofs;
+
+    #[test]
+    fn shared_nodes_are_sent_once() {
+        let authorities = generate_beefy_pairs_from_seed(5, 0);
+        let ids = authorities
+            .iter()
+            .map(|(_, id)| id.clone())
+            .collect::<Vec<_>>();
+        let blocks = ChainBuilder::with_seed(authorities, 0)
+            .push_block()
+            .push_block()
+            .push_block()
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        let claim_proof = blocks[4].build_claim_proof(&blocks[5], &blocks[5]);
+        let inclusion_proof = claim_proof.para_header_inclusion_proof.clone();
+        let kv_proof = claim_proof.kv_proof.clone();
+        let separate_size = inclusion_proof.encoded_size() + kv_proof.encoded_size();
+
+        // Proofs of relay block 5 come from different tries, so only indices are added
+        let bundle = DeduplicatedProofBundle::new(inclusion_proof.clone(), kv_proof.clone());
+        assert_eq!(bundle.para_inclusion_proof(), Some(inclusion_proof.clone()));
+        assert_eq!(bundle.kv_proof(), Some(kv_proof.clone()));
+        let indices = inclusion_proof.len() + kv_proof.len();
+        assert_eq!(bundle.nodes.len(), indices);
+        assert!(bundle.encoded_size() <= separate_size + 4 * indices + 2);
+
+        let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+        actor
+            .ingest_new_header(
+                blocks[5].ethereum_view(),
+                generate_signer_proofs(&ids, &[0, 1, 2, 3, 4]),
+            )
+            .unwrap();
+        assert_eq!(
+            actor.verify_claim_with_bundle(
+                None,
+                claim_proof.relay_header,
+                claim_proof.mmr_proof_items,
+                claim_proof.block_pos_in_mmr,
+                claim_proof.para_id,
+                claim_proof.para_header,
+                claim_proof.para_header_merkle_root,
+                claim_proof.claimed_kv,
+                bundle,
+            ),
+            Ok(())
+        );
+
+        // Nodes shared by both proofs are sent once
+        let bundle = DeduplicatedProofBundle::new(kv_proof.clone(), kv_proof.clone());
+        assert_eq!(bundle.nodes.len(), kv_proof.len());
+        assert!(bundle.encoded_size() < 2 * kv_proof.encoded_size());
+        println!(
+            "Bundle saves {} bytes out of {}",
+            2 * kv_proof.encoded_size() - bundle.encoded_size(),
+            2 * kv_proof.encoded_size()
+        );
+    }
+
+    #[test]
+    fn index_outside_of_nodes_is_rejected() {
+        let mut bundle = DeduplicatedProofBundle::new(vec![vec![1]], vec![vec![2]]);
+        bundle.kv_indices = vec![2];
+        assert_eq!(bundle.para_inclusion_proof(), Some(vec![vec![1]]));
+        assert_eq!(bundle.kv_proof(), None);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::ethereum_view::EthereumView;
+use crate::mmr::MMRNode;
+use crate::traits::BeefyLeaf;
+use codec::Encode;
+
+// Encoded byte sizes of the proofs relayer submits, to estimate calldata cost
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ProofSizeReport {
+    pub signed_commitment: usize,
+    pub para_header_inclusion_proof: usize,
+    pub chosen_kv_proof: usize,
+    pub mmr_proof_items: usize,
+}
+
+impl ProofSizeReport {
+    pub fn total(&self) -> usize {
+        self.signed_commitment
+            + self.para_header_inclusion_proof
+            + self.chosen_kv_proof
+            + self.mmr_proof_items
+    }
+}
+
+// MMR proof depends on the position being proven and is not part of the view,
+// so it is passed in separately
+pub fn proof_sizes<L: BeefyLeaf>(
+    view: &EthereumView<L>,
+    mmr_proof_items: &[MMRNode<L>],
+) -> ProofSizeReport {
+    ProofSizeReport {
+        signed_commitment: view.signed_commitment.encoded_size(),
+        para_header_inclusion_proof: view.para_header_merkle_proof.encoded_size(),
+        chosen_kv_proof: view.chosen_kv_proof.encoded_size(),
+        mmr_proof_items: mmr_proof_items.encoded_size(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_generation::ChainBuilder;
+    use crate::demo::generate_beefy_pairs_from_seed;
+
+    #[test]
+    fn report_adds_up_encoded_proofs() {
+        let blocks = ChainBuilder::with_seed(generate_beefy_pairs_from_seed(5, 0), 0)
+            .push_block()
+            .push_block()
+            .push_block()
+            .push_block()
+            .push_block()
+            .push_block_with_commitment()
+            .build();
+        // Claim of relay block 5 against MMR of relay block 7, which has 6 leaves
+        let claim_proof = blocks[4].build_claim_proof(&blocks[5], &blocks[6]);
+        let view = blocks[6].ethereum_view();
+        let report = proof_sizes(&view, &claim_proof.mmr_proof_items);
+        println!("Proof sizes for relay block 5: {:?}", report);
+
+        // Sibling leaf and the peak of first 4 leaves behind a compact length prefix,
+        // every node behind its variant byte
+        assert_eq!(claim_proof.mmr_proof_items.len(), 2);
+        let node_sizes = claim_proof
+            .mmr_proof_items
+            .iter()
+            .map(|node| match node {
+                MMRNode::Data(leaf) => 1 + leaf.encoded_size(),
+                MMRNode::Hash(_) => 1 + 32,
+            })
+            .sum::<usize>();
+        assert_eq!(report.mmr_proof_items, 1 + node_sizes);
+        assert_eq!(
+            report.signed_commitment,
+            view.signed_commitment.encoded_size()
+        );
+        assert_eq!(
+            report.total(),
+            report.signed_commitment
+                + report.para_header_inclusion_proof
+                + report.chosen_kv_proof
+                + report.mmr_proof_items
+        );
+
+        // View without a commitment only pays for the `None`
+        let report = proof_sizes(&blocks[5].ethereum_view(), &claim_proof.mmr_proof_items);
+        assert_eq!(report.signed_commitment, 1);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::commitment::{CommitmentPayload, PayloadId};
+use crate::ethereum_view::EthereumView;
+use crate::mmr::MMRNode;
+use crate::traits::{BeefyLeaf, Hashable};
+use crate::types::{HashOutput, ParaId, TestHeader};
+use codec::{Decode, Encode};
+use core::convert::TryFrom;
+use serde_crate::de::Error as _;
+use serde_crate::{Deserialize, Deserializer, Serialize, Serializer};
+use sp_core::sp_std::marker::PhantomData;
+use sp_core::Bytes;
+use std::string::ToString;
+use std::vec::Vec;
+
+// JSON representation meant for debugging and tooling, SCALE stays the wire format.
+// Byte fields are hex encoded, types without readable form (MMR nodes, signed
+// commitment) are hex encoded SCALE.
+
+fn decode_bytes<T: Decode, E: serde_crate::de::Error>(bytes: &Bytes) -> Result<T, E> {
+    T::decode(&mut &bytes[..]).map_err(|e| E::custom(e.to_string()))
+}
+
+fn to_bytes_list(items: &[Vec<u8>]) -> Vec<Bytes> {
+    items.iter().cloned().map(Bytes).collect()
+}
+
+fn from_bytes_list(items: Vec<Bytes>) -> Vec<Vec<u8>> {
+    items.into_iter().map(|item| item.0).collect()
+}
+
+impl<Leaf> Serialize for MMRNode<Leaf>
+where
+    Leaf: Hashable + Encode + Decode,
+    MMRNode<Leaf>: Encode,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Bytes(self.encode()).serialize(serializer)
+    }
+}
+
+impl<'de, Leaf> Deserialize<'de> for MMRNode<Leaf>
+where
+    Leaf: Hashable + Encode + Decode,
+    MMRNode<Leaf>: Decode,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        decode_bytes(&Bytes::deserialize(deserializer)?)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct CommitmentPayloadJson {
+    items: Vec<(Bytes, Bytes)>,
+    changed_authority_ids: Option<Vec<Bytes>>,
+    new_validator_set_id: u64,
+}
+
+impl<Leaf: Hashable + Encode + Decode> Serialize for CommitmentPayload<Leaf> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CommitmentPayloadJson {
+            items: self
+                .items
+                .iter()
+                .map(|(id, data)| (Bytes(id.to_vec()), Bytes(data.clone())))
+                .collect(),
+            changed_authority_ids: self
+                .changed_authority_ids
+                .as_ref()
+                .map(|ids| ids.iter().map(|id| Bytes(id.encode())).collect()),
+            new_validator_set_id: self.new_validator_set_id.into(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, Leaf: Hashable + Encode + Decode> Deserialize<'de> for CommitmentPayload<Leaf> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = CommitmentPayloadJson::deserialize(deserializer)?;
+        let changed_authority_ids = match json.changed_authority_ids {
+            Some(ids) => Some(
+                ids.iter()
+                    .map(decode_bytes)
+                    .collect::<Result<Vec<_>, D::Error>>()?,
+            ),
+            None => None,
+        };
+
+        // Items are inserted one by one, so that they end up sorted by id
+        let mut payload = CommitmentPayload {
+            items: vec![],
+            changed_authority_ids,
+            new_validator_set_id: json.new_validator_set_id.into(),
+            leaf: PhantomData,
+        };
+        for (id, data) in json.items {
+            let id = PayloadId::try_from(&id[..])
+                .map_err(|_| D::Error::custom("Payload id must be 2 bytes long"))?;
+            payload = payload.with_extra(id, data.0);
+        }
+        Ok(payload)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct EthereumViewJson {
+    beefy_mmr_root: Bytes,
+    beefy_mmr_leaves: u64,
+    relay_header: TestHeader,
+    // SCALE encoded
+    signed_commitment: Option<Bytes>,
+    para_id: ParaId,
+    para_header: TestHeader,
+    para_header_merkle_proof: Vec<Bytes>,
+    para_header_merkle_root: HashOutput,
+    chosen_kv_proof: Vec<Bytes>,
+    chosen_kv_pair: (Bytes, Bytes),
+}
+
+impl<L: BeefyLeaf> Serialize for EthereumView<L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        EthereumViewJson {
+            beefy_mmr_root: Bytes(self.beefy_mmr_root.encode()),
+            beefy_mmr_leaves: self.beefy_mmr_leaves,
+            relay_header: self.relay_header.clone(),
+            signed_commitment: self
+                .signed_commitment
+                .as_ref()
+                .map(|signed_commitment| Bytes(signed_commitment.encode())),
+            para_id: self.para_id,
+            para_header: self.para_header.clone(),
+            para_header_merkle_proof: to_bytes_list(&self.para_header_merkle_proof),
+            para_header_merkle_root: self.para_header_merkle_root,
+            chosen_kv_proof: to_bytes_list(&self.chosen_kv_proof),
+            chosen_kv_pair: (
+                Bytes(self.chosen_kv_pair.0.clone()),
+                Bytes(self.chosen_kv_pair.1.clone()),
+            ),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, L: BeefyLeaf> Deserialize<'de> for EthereumView<L> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = EthereumViewJson::deserialize(deserializer)?;
+        Ok(EthereumView {
+            beefy_mmr_root: decode_bytes(&json.beefy_mmr_root)?,
+            beefy_mmr_leaves: json.beefy_mmr_leaves,
+            relay_header: json.relay_header,
+            signed_commitment: match json.signed_commitment {
+                Some(signed_commitment) => Some(decode_bytes(&signed_commitment)?),
+                None => None,
+            },
+            para_id: json.para_id,
+            para_header: json.para_header,
+            para_header_merkle_proof: from_bytes_list(json.para_header_merkle_proof),
+            para_header_merkle_root: json.para_header_merkle_root,
+            chosen_kv_proof: from_bytes_list(json.chosen_kv_proof),
+            chosen_kv_pair: (json.chosen_kv_pair.0 .0, json.chosen_kv_pair.1 .0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_generation::ChainBuilder;
+    use crate::demo::generate_beefy_pairs_from_seed;
+    use crate::types::{LeafData, ValidatorSetId};
+
+    #[test]
+    fn view_survives_json_round_trip() {
+        let blocks = ChainBuilder::with_seed(generate_beefy_pairs_from_seed(5, 0), 0)
+            .push_block()
+            .rotate_authorities(generate_beefy_pairs_from_seed(5, 1))
+            .push_block_with_commitment()
+            .build();
+
+        // Views without commitment, and with one enacting a handover
+        for block in blocks.iter() {
+            let view = block.ethereum_view();
+            let json = serde_json::to_string(&view).unwrap();
+            let decoded: EthereumView<LeafData> = serde_json::from_str(&json).unwrap();
+            assert!(decoded == view);
+        }
+    }
+
+    #[test]
+    fn payload_id_of_wrong_length_is_rejected() {
+        let payload = CommitmentPayload::<LeafData>::new(
+            MMRNode::Hash(Default::default()),
+            None,
+            ValidatorSetId(0),
+        )
+        .with_extra(*b"m2", vec![1, 2, 3]);
+        let mut json = serde_json::to_value(&payload).unwrap();
+        let decoded: CommitmentPayload<LeafData> = serde_json::from_value(json.clone()).unwrap();
+        assert!(decoded == payload);
+
+        json["items"][0][0] = "0x6d6d6d".into();
+        let error = serde_json::from_value::<CommitmentPayload<LeafData>>(json)
+            .map(|_| ())
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Payload id must be 2 bytes long"));
+    }
+
+    #[test]
+    fn undecodable_view_bytes_are_rejected() {
+        let blocks = ChainBuilder::with_seed(generate_beefy_pairs_from_seed(5, 0), 0)
+            .push_block_with_commitment()
+            .build();
+        let mut json = serde_json::to_value(&blocks[1].ethereum_view()).unwrap();
+        // Truncated SCALE encoding of the signed commitment
+        json["signed_commitment"] = "0x01".into();
+
+        assert!(serde_json::from_value::<EthereumView<LeafData>>(json).is_err());
+    }
+}
@@ -1,6 +1,43 @@
-use std::fmt::Debug;
+use crate::types::{BlockNumber, HashOutput, ParaId, TestHeader};
+use alloc::vec::Vec;
+use codec::{Decode, Encode};
+use core::fmt::Debug;
 
 pub trait Hashable {
     type Out: AsRef<[u8]> + AsMut<[u8]> + Copy + PartialEq + Debug;
-    fn hash(&self) -> Self::Out;
+
+    fn hash(&self) -> Self::Out {
+        self.hash_with_domain(&[])
+    }
+
+    // Empty domain must hash the same as `hash`
+    fn hash_with_domain(&self, domain: &[u8]) -> Self::Out;
+}
+
+// Tag mixed into leaf and node hashes of an MMR, so that MMRs sharing
+// leaf type and hasher cannot end up with colliding roots
+pub trait MmrDomain {
+    const TAG: &'static [u8];
+}
+
+// No tag, hashes are the same as without domain separation
+pub struct NoDomain;
+
+impl MmrDomain for NoDomain {
+    const TAG: &'static [u8] = &[];
+}
+
+// How para heads are keyed in para heads merkle tree
+pub trait ParaHeadKey {
+    fn key(para_id: ParaId, para_header: &TestHeader) -> Vec<u8>;
+}
+
+// Beefy MMR leaf schema, the actor only relies on these to verify claims
+pub trait BeefyLeaf:
+    Hashable<Out = HashOutput> + Encode + Decode + Clone + PartialEq + Debug
+{
+    fn relay_block_number(&self) -> BlockNumber;
+    fn para_heads_root(&self) -> HashOutput;
+    // Leaf of the relay block, None if header lacks data needed to build it
+    fn from_relay_block(header: &TestHeader, para_heads_root: HashOutput) -> Option<Self>;
 }
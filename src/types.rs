@@ -1,13 +1,124 @@
+use alloc::vec::Vec;
+use beefy_primitives::crypto::AuthorityId;
+use codec::{Decode, Encode};
+use core::fmt;
+use sp_core::sp_std::marker::PhantomData;
 use sp_core::Hasher;
 use sp_runtime::generic::Header;
 use sp_runtime::traits::BlakeTwo256;
 
-pub type BlockNumber = u64;
+pub type ParaId = u32;
 pub type HashingAlgo = BlakeTwo256;
-pub type TestHeader = Header<BlockNumber, HashingAlgo>;
+// Header needs an arithmetic block number, so headers (as well as commitments
+// and MMR leaves, which mirror chain encodings) number blocks with bare `u64`
+pub type TestHeader = Header<u64, HashingAlgo>;
 
 pub type HashOutput = <HashingAlgo as Hasher>::Out;
 
 pub type TrieLayout = sp_trie::Layout<sp_core::KeccakHasher>;
 
-pub type LeafData = (BlockNumber, HashOutput, HashOutput);
+// Block numbers, set ids and MMR positions are all `u64`, distinct types keep
+// them from being passed one for another in security critical calls
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Encode, Decode)]
+pub struct BlockNumber(pub u64);
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Encode, Decode)]
+pub struct ValidatorSetId(pub u64);
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Encode, Decode)]
+pub struct MmrPosition(pub u64);
+
+impl ValidatorSetId {
+    // Id of the set enacted by the next handover
+    pub fn next(self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
+macro_rules! impl_u64_newtype {
+    ($($name:ident),*) => {$(
+        impl From<u64> for $name {
+            fn from(value: u64) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<$name> for u64 {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    )*};
+}
+
+impl_u64_newtype!(BlockNumber, ValidatorSetId, MmrPosition);
+
+// Beefy MMR leaf of a relay block. Like `beefy_next_authority_set` of BEEFY leaves,
+// it carries the authority set which signs commitments after this block.
+// Leaf is hashed with `H`, which is not part of the encoding.
+#[derive(Encode, Decode)]
+pub struct LeafData<H = HashingAlgo> {
+    pub block_number: u64,
+    pub block_hash: HashOutput,
+    pub para_heads_root: HashOutput,
+    pub next_authority_set_id: u64,
+    pub next_authority_set_len: u32,
+    pub next_authority_set_root: HashOutput,
+    #[codec(skip)]
+    pub hasher: PhantomData<H>,
+}
+
+// Implemented by hand, derives would require the hasher to implement them too
+impl<H> Clone for LeafData<H> {
+    fn clone(&self) -> Self {
+        Self {
+            block_number: self.block_number,
+            block_hash: self.block_hash,
+            para_heads_root: self.para_heads_root,
+            next_authority_set_id: self.next_authority_set_id,
+            next_authority_set_len: self.next_authority_set_len,
+            next_authority_set_root: self.next_authority_set_root,
+            hasher: PhantomData,
+        }
+    }
+}
+
+impl<H> PartialEq for LeafData<H> {
+    fn eq(&self, other: &Self) -> bool {
+        self.block_number == other.block_number
+            && self.block_hash == other.block_hash
+            && self.para_heads_root == other.para_heads_root
+            && self.next_authority_set_id == other.next_authority_set_id
+            && self.next_authority_set_len == other.next_authority_set_len
+            && self.next_authority_set_root == other.next_authority_set_root
+    }
+}
+
+impl<H> fmt::Debug for LeafData<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LeafData")
+            .field("block_number", &self.block_number)
+            .field("block_hash", &self.block_hash)
+            .field("para_heads_root", &self.para_heads_root)
+            .field("next_authority_set_id", &self.next_authority_set_id)
+            .field("next_authority_set_len", &self.next_authority_set_len)
+            .field("next_authority_set_root", &self.next_authority_set_root)
+            .finish()
+    }
+}
+
+// Index of the authority in the set, its id and proof of inclusion in authority merkle root
+pub type SignerProof = (u32, AuthorityId, Vec<Vec<u8>>);
+
+// Validators which joined and left the authority set during a handover
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct AuthoritySetDiff {
+    pub added: Vec<AuthorityId>,
+    pub removed: Vec<AuthorityId>,
+}
@@ -1,9 +1,28 @@
-use std::vec::Vec;
+use alloc::vec::Vec;
 
-use sp_core::Hasher;
+use beefy_primitives::crypto::{AuthorityId, AuthoritySignature};
+use beefy_primitives::BEEFY_ENGINE_ID;
+use codec::{Decode, Encode};
+use mmr_lib::util::{MemMMR, MemStore};
+use sp_core::ecdsa;
+use sp_core::hashing::{keccak_256, twox_128, twox_64};
+use sp_core::sp_std::marker::PhantomData;
+use sp_core::{Hasher, KeccakHasher};
+use sp_runtime::generic::DigestItem;
+use sp_runtime::ConsensusEngineId;
+#[cfg(feature = "std")]
+use sp_trie::Layout;
+use sp_trie::{MemoryDB, TrieDBMut, TrieMut};
 
-use crate::traits::Hashable;
-use crate::types::{HashOutput, HashingAlgo, LeafData};
+#[cfg(feature = "std")]
+use crate::block_data::ParaHeadEntry;
+use crate::commitment::SignerMultiproof;
+use crate::mmr::{MMRNode, MergeStrategy};
+use crate::traits::{BeefyLeaf, Hashable, ParaHeadKey};
+use crate::types::{
+    AuthoritySetDiff, BlockNumber, HashOutput, HashingAlgo, LeafData, MmrPosition, ParaId,
+    SignerProof, TestHeader, TrieLayout,
+};
 
 pub fn mmr_size_from_number_of_leaves(leaves: u64) -> u64 {
     if leaves == 0 {
@@ -13,14 +32,529 @@ pub fn mmr_size_from_number_of_leaves(leaves: u64) -> u64 {
     }
 }
 
-impl Hashable for LeafData {
+// Upper bound of proof items in a single leaf MMR proof, to budget calldata before
+// generating any proof. Proof holds siblings on the path to the leaf's peak, peaks
+// left of it and bagged peaks right of it. Peak heights strictly decrease, so a leaf
+// of the tallest (leftmost) peak needs the most items.
+pub fn max_mmr_proof_items(leaf_count: u64) -> usize {
+    if leaf_count == 0 {
+        return 0;
+    }
+    let tallest_peak_height = 63 - leaf_count.leading_zeros() as usize;
+    let bagged_peaks = if leaf_count.count_ones() > 1 { 1 } else { 0 };
+    tallest_peak_height + bagged_peaks
+}
+
+// Position of the MMR leaf of given relay block. Leaves are added for every block
+// starting from genesis, so block `genesis_number + i` is at leaf index `i`.
+// `None` if block is before genesis.
+pub fn mmr_position_for_block(
+    block_number: BlockNumber,
+    genesis_number: BlockNumber,
+) -> Option<MmrPosition> {
+    let leaf_index = block_number.0.checked_sub(genesis_number.0)?;
+    Some(mmr_lib::leaf_index_to_pos(leaf_index).into())
+}
+
+// Recomputes beefy MMR root from scratch, useful as a reference when cross-checking
+// other MMR implementations. Panics if `leaves` is empty.
+pub fn compute_mmr_root<H: Hasher<Out = HashOutput>>(
+    leaves: &[LeafData<H>],
+) -> MMRNode<LeafData<H>> {
+    let mut mmr = MemMMR::<_, MergeStrategy<LeafData<H>, H>>::new(0, MemStore::default());
+    for leaf in leaves {
+        mmr.push(MMRNode::Data(leaf.clone())).unwrap();
+    }
+    mmr.get_root().unwrap()
+}
+
+// Root of the MMR without leaves (e.g. of genesis), which `mmr_lib` refuses to compute
+pub fn empty_mmr_root<L: BeefyLeaf>() -> MMRNode<L> {
+    MMRNode::Hash(Default::default())
+}
+
+// MMR root is committed in relay header as beefy consensus digest
+pub fn mmr_root_digest_item<L: BeefyLeaf>(mmr_root: &MMRNode<L>) -> DigestItem<HashOutput> {
+    DigestItem::Consensus(BEEFY_ENGINE_ID, mmr_root.encode())
+}
+
+// First beefy consensus digest in the header that decodes to MMR root
+pub fn mmr_root_from_digest<L: BeefyLeaf>(header: &TestHeader) -> Option<MMRNode<L>> {
+    header.digest.logs().iter().find_map(|item| match item {
+        DigestItem::Consensus(engine_id, data) if *engine_id == BEEFY_ENGINE_ID => {
+            MMRNode::<L>::decode(&mut &data[..]).ok()
+        }
+        _ => None,
+    })
+}
+
+// Engine id of the digest announcing authority set which signs commitments after the block
+pub const AUTHORITY_SET_ENGINE_ID: ConsensusEngineId = *b"BSET";
+
+// Authority set is announced as its id, length and merkle root
+pub fn authority_set_digest_item(
+    set_id: u64,
+    authorities: &[AuthorityId],
+) -> DigestItem<HashOutput> {
+    DigestItem::Consensus(
+        AUTHORITY_SET_ENGINE_ID,
+        (
+            set_id,
+            authorities.len() as u32,
+            build_authority_merkle_root(authorities),
+        )
+            .encode(),
+    )
+}
+
+pub fn authority_set_from_digest(header: &TestHeader) -> Option<(u64, u32, HashOutput)> {
+    header.digest.logs().iter().find_map(|item| match item {
+        DigestItem::Consensus(engine_id, data) if *engine_id == AUTHORITY_SET_ENGINE_ID => {
+            <(u64, u32, HashOutput)>::decode(&mut &data[..]).ok()
+        }
+        _ => None,
+    })
+}
+
+// MMR leaf of the relay block, None if header does not announce its authority set
+pub fn leaf_for_relay_block<H>(
+    header: &TestHeader,
+    para_heads_root: HashOutput,
+) -> Option<LeafData<H>> {
+    authority_set_from_digest(header).map(|(set_id, set_len, set_root)| LeafData {
+        block_number: header.number,
+        block_hash: header.hash(),
+        para_heads_root,
+        next_authority_set_id: set_id,
+        next_authority_set_len: set_len,
+        next_authority_set_root: set_root,
+        hasher: PhantomData,
+    })
+}
+
+// Key of the para head in para heads merkle tree, binds header to its parachain
+pub fn para_head_key<H: Hasher<Out = HashOutput>>(
+    para_id: ParaId,
+    para_header: &TestHeader,
+) -> HashOutput {
+    H::hash(&(para_id, para_header.hash()).encode())
+}
+
+// Para heads keyed by `para_head_key` hashed with `H`, this is the default
+#[derive(Clone, Debug)]
+pub struct HashKeyed<H = HashingAlgo>(PhantomData<H>);
+
+impl<H: Hasher<Out = HashOutput>> ParaHeadKey for HashKeyed<H> {
+    fn key(para_id: ParaId, para_header: &TestHeader) -> Vec<u8> {
+        para_head_key::<H>(para_id, para_header).as_bytes().to_vec()
+    }
+}
+
+// Para heads keyed like `Paras::Heads` storage map of the relay chain
+// (twox128 prefixes and twox64 concat para id), so proofs are shaped like on mainnet
+#[derive(Clone, Debug)]
+pub struct RealStorageKeyed;
+
+impl ParaHeadKey for RealStorageKeyed {
+    fn key(para_id: ParaId, _para_header: &TestHeader) -> Vec<u8> {
+        let encoded_para_id = para_id.encode();
+        let mut key = twox_128(b"Paras").to_vec();
+        key.extend_from_slice(&twox_128(b"Heads"));
+        key.extend_from_slice(&twox_64(&encoded_para_id));
+        key.extend_from_slice(&encoded_para_id);
+        key
+    }
+}
+
+// Para heads merkle tree, shared by block generation and claim proof generation
+// so that proofs are always built the same way the verifier expects
+#[cfg(feature = "std")]
+pub fn build_para_heads_trie<H: Hasher<Out = HashOutput>>(
+    entries: &[ParaHeadEntry],
+) -> (HashOutput, MemoryDB<H>) {
+    let mut memdb = MemoryDB::<H>::default();
+    let mut para_heads_root = Default::default();
+    {
+        let mut trie_db = TrieDBMut::<Layout<H>>::new(&mut memdb, &mut para_heads_root);
+        for entry in entries.iter() {
+            trie_db.insert(&entry.key, &entry.encoded_header).unwrap();
+        }
+    }
+    (para_heads_root, memdb)
+}
+
+#[cfg(feature = "std")]
+pub fn prove_para_head_inclusion<H: Hasher<Out = HashOutput>>(
+    memdb: &MemoryDB<H>,
+    para_heads_root: HashOutput,
+    para_head_key: Vec<u8>,
+) -> Vec<Vec<u8>> {
+    sp_trie::generate_trie_proof::<Layout<H>, _, _, _>(memdb, para_heads_root, vec![para_head_key])
+        .unwrap()
+}
+
+fn build_authority_trie(authorities: &[AuthorityId]) -> (MemoryDB<KeccakHasher>, HashOutput) {
+    let mut memdb = MemoryDB::<KeccakHasher>::default();
+    let mut authority_root = Default::default();
+    {
+        let mut trie_db = TrieDBMut::<TrieLayout>::new(&mut memdb, &mut authority_root);
+        for (i, authority) in authorities.iter().enumerate() {
+            trie_db
+                .insert(&(i as u32).encode(), &authority.encode())
+                .unwrap();
+        }
+    }
+    (memdb, authority_root)
+}
+
+// Authorities are keyed by their (SCALE encoded) index in the set
+pub fn build_authority_merkle_root(authorities: &[AuthorityId]) -> HashOutput {
+    build_authority_trie(authorities).1
+}
+
+// Authorities are compared by id, position in the set does not matter
+pub fn authority_set_diff(old: &[AuthorityId], new: &[AuthorityId]) -> AuthoritySetDiff {
+    AuthoritySetDiff {
+        added: new.iter().filter(|id| !old.contains(id)).cloned().collect(),
+        removed: old.iter().filter(|id| !new.contains(id)).cloned().collect(),
+    }
+}
+
+// Ethereum address of an authority, i.e. last 20 bytes of keccak256 of its uncompressed
+// public key (without the `0x04` prefix). Panics if the id is not a valid secp256k1 key.
+pub fn authority_eth_address(authority: &AuthorityId) -> [u8; 20] {
+    let public = libsecp256k1::PublicKey::parse_slice(
+        AsRef::<ecdsa::Public>::as_ref(authority).as_ref(),
+        Some(libsecp256k1::PublicKeyFormat::Compressed),
+    )
+    .expect("Authority id must be a valid secp256k1 public key");
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&keccak_256(&public.serialize()[1..])[12..]);
+    address
+}
+
+// Initial authority set of the light client contract, `root` and `length` of the
+// Solidity `ValidatorSet { id, length, root }`. Root is the binary merkle root over
+// keccak256 of authority addresses, same as `beefy-merkle-tree`, and not the trie root
+// of `build_authority_merkle_root` which signers of this crate prove membership against.
+pub fn authority_set_commitment(authorities: &[AuthorityId]) -> (HashOutput, u32) {
+    let leaves = authorities
+        .iter()
+        .map(|authority| keccak_256(&authority_eth_address(authority)))
+        .collect();
+    (binary_merkle_root(leaves), authorities.len() as u32)
+}
+
+// Pairs of nodes are hashed with keccak256, last node of a layer with odd number of
+// nodes is promoted to the next layer as is. Root of no leaves is zero hash.
+fn binary_merkle_root(leaves: Vec<[u8; 32]>) -> HashOutput {
+    let mut layer = leaves;
+    while layer.len() > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => keccak_256(&[&left[..], &right[..]].concat()),
+                [single] => *single,
+                _ => unreachable!("Chunks have one or two nodes"),
+            })
+            .collect();
+    }
+    layer
+        .first()
+        .copied()
+        .map(HashOutput::from)
+        .unwrap_or_default()
+}
+
+pub fn generate_signer_proofs(
+    authorities: &[AuthorityId],
+    signer_indices: &[u32],
+) -> Vec<SignerProof> {
+    let (memdb, authority_root) = build_authority_trie(authorities);
+    signer_indices
+        .iter()
+        .map(|index| {
+            let key = index.encode();
+            let proof = sp_trie::generate_trie_proof::<TrieLayout, _, _, _>(
+                &memdb,
+                authority_root,
+                vec![&key],
+            )
+            .unwrap();
+            (*index, authorities[*index as usize].clone(), proof)
+        })
+        .collect()
+}
+
+// Single multiproof for all signers, signatures are taken from the signed commitment.
+// Panics if any of the signers did not sign.
+pub fn generate_signer_multiproof(
+    authorities: &[AuthorityId],
+    signatures: &[Option<AuthoritySignature>],
+    signer_indices: &[u32],
+) -> SignerMultiproof {
+    let (memdb, authority_root) = build_authority_trie(authorities);
+    let keys = signer_indices
+        .iter()
+        .map(|index| index.encode())
+        .collect::<Vec<_>>();
+    let proof =
+        sp_trie::generate_trie_proof::<TrieLayout, _, _, _>(&memdb, authority_root, keys.iter())
+            .unwrap();
+    SignerMultiproof {
+        signers: signer_indices
+            .iter()
+            .map(|index| {
+                (
+                    *index,
+                    authorities[*index as usize].clone(),
+                    signatures[*index as usize].clone().unwrap(),
+                )
+            })
+            .collect(),
+        proof,
+    }
+}
+
+// Prepended to every hashed leaf to separate leaf schemas.
+// Bumping it changes every leaf hash and thus every MMR root.
+pub const LEAF_VERSION: u8 = 1;
+
+impl<H: Hasher<Out = HashOutput>> Hashable for LeafData<H> {
     type Out = HashOutput;
 
-    fn hash(&self) -> Self::Out {
-        let mut payload: Vec<u8> = vec![];
-        payload.append(&mut self.0.to_le_bytes().to_vec());
-        payload.append(&mut self.1.as_bytes().to_vec());
-        payload.append(&mut self.2.as_bytes().to_vec());
-        HashingAlgo::hash(payload.as_slice())
+    fn hash_with_domain(&self, domain: &[u8]) -> Self::Out {
+        let mut payload: Vec<u8> = vec![LEAF_VERSION];
+        payload.extend_from_slice(domain);
+        payload.append(&mut self.block_number.to_le_bytes().to_vec());
+        payload.append(&mut self.block_hash.as_bytes().to_vec());
+        payload.append(&mut self.para_heads_root.as_bytes().to_vec());
+        payload.append(&mut self.next_authority_set_id.to_le_bytes().to_vec());
+        payload.append(&mut self.next_authority_set_len.to_le_bytes().to_vec());
+        payload.append(&mut self.next_authority_set_root.as_bytes().to_vec());
+        H::hash(payload.as_slice())
+    }
+}
+
+impl<H: Hasher<Out = HashOutput>> BeefyLeaf for LeafData<H> {
+    fn relay_block_number(&self) -> BlockNumber {
+        BlockNumber(self.block_number)
+    }
+
+    fn para_heads_root(&self) -> HashOutput {
+        self.para_heads_root
+    }
+
+    fn from_relay_block(header: &TestHeader, para_heads_root: HashOutput) -> Option<Self> {
+        leaf_for_relay_block(header, para_heads_root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo::generate_beefy_pairs_from_seed;
+    use crate::error::VerificationError;
+    use crate::ethereum_actor::verify_para_head_proof;
+    use beefy_primitives::crypto::Pair;
+    use sp_core::crypto::Pair as _;
+
+    fn authority_ids(number: usize) -> Vec<AuthorityId> {
+        generate_beefy_pairs_from_seed(number, 0)
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect()
+    }
+
+    #[test]
+    fn authority_set_commitment_is_binary_merkle_root_of_addresses() {
+        let authorities = authority_ids(3);
+        let leaf = |index: usize| keccak_256(&authority_eth_address(&authorities[index]));
+        // Third leaf has no sibling and is promoted
+        let expected =
+            keccak_256(&[&keccak_256(&[leaf(0), leaf(1)].concat())[..], &leaf(2)[..]].concat());
+
+        assert_eq!(
+            authority_set_commitment(&authorities),
+            (HashOutput::from(expected), 3)
+        );
+        assert_eq!(
+            authority_set_commitment(&authorities),
+            authority_set_commitment(&authority_ids(3))
+        );
+    }
+
+    #[test]
+    fn reordering_authorities_changes_commitment() {
+        let authorities = authority_ids(5);
+        let mut reordered = authorities.clone();
+        reordered.swap(1, 3);
+
+        assert_ne!(
+            authority_set_commitment(&authorities).0,
+            authority_set_commitment(&reordered).0
+        );
+    }
+
+    #[test]
+    fn authority_address_matches_ethereum() {
+        // Secret key 1 is the well known address 0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let authority = Pair::from_seed(&secret).public();
+
+        assert_eq!(
+            authority_eth_address(&authority),
+            [
+                0x7e, 0x5f, 0x45, 0x52, 0x09, 0x1a, 0x69, 0x12, 0x5d, 0x5d, 0xfc, 0xb7, 0xb8, 0xc2,
+                0x65, 0x90, 0x29, 0x39, 0x5b, 0xdf
+            ]
+        );
+    }
+
+    #[test]
+    fn max_mmr_proof_items_is_longest_leaf_proof() {
+        assert_eq!(max_mmr_proof_items(0), 0);
+
+        let mut mmr =
+            MemMMR::<_, MergeStrategy<LeafData, HashingAlgo>>::new(0, MemStore::default());
+        for leaf_count in 1..=1000u64 {
+            mmr.push(MMRNode::Data(LeafData {
+                block_number: leaf_count,
+                block_hash: HashOutput::from_low_u64_be(leaf_count),
+                para_heads_root: Default::default(),
+                next_authority_set_id: 0,
+                next_authority_set_len: 0,
+                next_authority_set_root: Default::default(),
+                hasher: PhantomData,
+            }))
+            .unwrap();
+
+            let proof_lengths = (0..leaf_count)
+                .map(|leaf_index| {
+                    mmr.gen_proof(vec![mmr_lib::leaf_index_to_pos(leaf_index)])
+                        .unwrap()
+                        .proof_items()
+                        .len()
+                })
+                .collect::<Vec<usize>>();
+            // Bound holds for every leaf and is reached by leaves of the tallest peak
+            assert_eq!(
+                proof_lengths.iter().max(),
+                Some(&max_mmr_proof_items(leaf_count))
+            );
+        }
+    }
+
+    #[test]
+    fn block_position_is_counted_from_genesis() {
+        let genesis = BlockNumber(10);
+        assert_eq!(
+            mmr_position_for_block(genesis, genesis),
+            Some(MmrPosition(0))
+        );
+        // Leaves 0, 1, 2 take positions 0, 1, 3 and their parent 2
+        assert_eq!(
+            mmr_position_for_block(BlockNumber(13), genesis),
+            Some(MmrPosition(4))
+        );
+        assert_eq!(mmr_position_for_block(BlockNumber(9), genesis), None);
+        assert_eq!(mmr_position_for_block(BlockNumber(0), genesis), None);
+    }
+
+    #[test]
+    fn para_head_is_proven_for_its_own_para() {
+        let headers = (1..=4)
+            .map(|number| TestHeader {
+                parent_hash: Default::default(),
+                number,
+                state_root: HashOutput::repeat_byte(number as u8),
+                extrinsics_root: Default::default(),
+                digest: Default::default(),
+            })
+            .collect::<Vec<TestHeader>>();
+        let entries = headers
+            .iter()
+            .enumerate()
+            .map(|(i, header)| ParaHeadEntry::new::<HashKeyed>(2000 + i as ParaId, header))
+            .collect::<Vec<ParaHeadEntry>>();
+        let (root, memdb) = build_para_heads_trie::<HashingAlgo>(&entries);
+        let proof = prove_para_head_inclusion(
+            &memdb,
+            root,
+            HashKeyed::<HashingAlgo>::key(2001, &headers[1]),
+        );
+
+        let verify = |para_id: ParaId, header: &TestHeader| {
+            verify_para_head_proof::<HashingAlgo, HashKeyed>(para_id, header, root, proof.clone())
+        };
+        assert_eq!(verify(2001, &headers[1]), Ok(()));
+        assert_eq!(
+            verify(2000, &headers[1]),
+            Err(VerificationError::ParaInclusionFailed)
+        );
+        assert_eq!(
+            verify(2001, &headers[2]),
+            Err(VerificationError::ParaInclusionFailed)
+        );
+    }
+
+    #[test]
+    fn reordered_set_has_no_joined_or_left_validators() {
+        let authorities = authority_ids(5);
+        let mut reordered = authorities.clone();
+        reordered.swap(1, 3);
+
+        assert_eq!(
+            authority_set_diff(&authorities, &reordered),
+            AuthoritySetDiff::default()
+        );
+    }
+
+    #[test]
+    fn real_storage_key_is_paras_heads_key() {
+        let para_header = TestHeader {
+            parent_hash: Default::default(),
+            number: 1,
+            state_root: Default::default(),
+            extrinsics_root: Default::default(),
+            digest: Default::default(),
+        };
+
+        // twox128("Paras") ++ twox128("Heads") ++ twox64(2000) ++ 2000 as on the relay chain
+        assert_eq!(
+            RealStorageKeyed::key(2000, &para_header),
+            vec![
+                0xcd, 0x71, 0x0b, 0x30, 0xbd, 0x2e, 0xab, 0x03, 0x52, 0xdd, 0xcc, 0x26, 0x41, 0x7a,
+                0xa1, 0x94, 0x1b, 0x3c, 0x25, 0x2f, 0xcb, 0x29, 0xd8, 0x8e, 0xff, 0x4f, 0x3d, 0xe5,
+                0xde, 0x44, 0x76, 0xc3, 0x63, 0xf5, 0xa4, 0xef, 0xb1, 0x6f, 0xfa, 0x83, 0xd0, 0x07,
+                0x00, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn leaf_hash_is_separated_by_version() {
+        let leaf = LeafData::<HashingAlgo> {
+            block_number: 7,
+            block_hash: HashOutput::repeat_byte(1),
+            para_heads_root: HashOutput::repeat_byte(2),
+            next_authority_set_id: 1,
+            next_authority_set_len: 5,
+            next_authority_set_root: HashOutput::repeat_byte(3),
+            hasher: PhantomData,
+        };
+        let mut unversioned = leaf.block_number.to_le_bytes().to_vec();
+        unversioned.extend_from_slice(leaf.block_hash.as_bytes());
+        unversioned.extend_from_slice(leaf.para_heads_root.as_bytes());
+        unversioned.extend_from_slice(&leaf.next_authority_set_id.to_le_bytes());
+        unversioned.extend_from_slice(&leaf.next_authority_set_len.to_le_bytes());
+        unversioned.extend_from_slice(leaf.next_authority_set_root.as_bytes());
+
+        assert_ne!(leaf.hash(), HashingAlgo::hash(&unversioned));
+        assert_eq!(
+            leaf.hash(),
+            HashingAlgo::hash(&[&[LEAF_VERSION][..], &unversioned[..]].concat())
+        );
     }
 }
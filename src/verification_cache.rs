@@ -0,0 +1,106 @@
+use crate::types::HashOutput;
+use alloc::collections::BTreeMap;
+
+// MMR root hash, position of the leaf and hash of the leaf
+pub type MmrProofKey = (HashOutput, u64, HashOutput);
+
+// Remembers MMR proofs which verified successfully, least recently used one
+// is evicted once capacity is reached
+#[derive(Clone)]
+pub struct VerificationCache {
+    // Entries along with the tick of their last use
+    last_used: BTreeMap<MmrProofKey, u64>,
+    // Same entries keyed by the tick of their last use, least recently used first
+    by_last_use: BTreeMap<u64, MmrProofKey>,
+    tick: u64,
+    capacity: usize,
+}
+
+impl VerificationCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            last_used: BTreeMap::new(),
+            by_last_use: BTreeMap::new(),
+            tick: 0,
+            capacity,
+        }
+    }
+
+    // Marks the entry as most recently used if found
+    pub fn contains(&mut self, key: &MmrProofKey) -> bool {
+        let tick = self.tick;
+        match self.last_used.get_mut(key) {
+            Some(last_used) => {
+                self.by_last_use.remove(last_used);
+                self.by_last_use.insert(tick, *key);
+                *last_used = tick;
+                self.tick += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn insert(&mut self, key: MmrProofKey) {
+        if self.capacity == 0 || self.contains(&key) {
+            return;
+        }
+        if self.last_used.len() == self.capacity {
+            let least_recently_used = *self.by_last_use.keys().next().unwrap();
+            let evicted = self.by_last_use.remove(&least_recently_used).unwrap();
+            self.last_used.remove(&evicted);
+        }
+        self.last_used.insert(key, self.tick);
+        self.by_last_use.insert(self.tick, key);
+        self.tick += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> MmrProofKey {
+        (
+            HashOutput::repeat_byte(byte),
+            byte as u64,
+            HashOutput::repeat_byte(byte),
+        )
+    }
+
+    #[test]
+    fn inserted_entry_is_hit() {
+        let mut cache = VerificationCache::new(2);
+        assert!(!cache.contains(&key(1)));
+        cache.insert(key(1));
+        assert!(cache.contains(&key(1)));
+        assert!(!cache.contains(&key(2)));
+
+        // Inserting the same entry again does not take more space
+        cache.insert(key(1));
+        cache.insert(key(2));
+        assert!(cache.contains(&key(1)));
+        assert!(cache.contains(&key(2)));
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        let mut cache = VerificationCache::new(2);
+        cache.insert(key(1));
+        cache.insert(key(2));
+        // Hit makes the first entry most recently used
+        assert!(cache.contains(&key(1)));
+        cache.insert(key(3));
+
+        assert!(cache.contains(&key(1)));
+        assert!(!cache.contains(&key(2)));
+        assert!(cache.contains(&key(3)));
+    }
+
+    #[test]
+    fn zero_capacity_cache_is_always_missed() {
+        let mut cache = VerificationCache::new(0);
+        cache.insert(key(1));
+        assert!(!cache.contains(&key(1)));
+    }
+}
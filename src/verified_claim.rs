@@ -0,0 +1,12 @@
+use crate::types::{BlockNumber, HashOutput, ParaId};
+use alloc::vec::Vec;
+use codec::{Decode, Encode};
+
+// Para block data proven by `EthereumActor::verify_claim_extract`
+#[derive(Clone, PartialEq, Debug, Encode, Decode)]
+pub struct VerifiedClaim {
+    pub para_id: ParaId,
+    pub para_block_number: BlockNumber,
+    pub para_state_root: HashOutput,
+    pub claimed_kv: (Vec<u8>, Vec<u8>),
+}
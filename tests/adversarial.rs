@@ -0,0 +1,80 @@
+// Every link of the claim (MMR -> para inclusion -> storage) must reject tampering
+// on its own, a forged component must never slip through to a successful claim
+
+use beefy_lc_demo::{
+    generate_beefy_pairs_from_seed, generate_signer_proofs, BlockData, ChainBuilder, ClaimProof,
+    EthereumActor, VerificationError,
+};
+use sp_core::H256;
+
+// Actor which finalized the 7th block and a valid claim of the 4th block
+fn setup() -> (EthereumActor, Vec<BlockData>, ClaimProof) {
+    let authorities = generate_beefy_pairs_from_seed(5, 0);
+    let authority_ids = authorities
+        .iter()
+        .map(|(_, id)| id.clone())
+        .collect::<Vec<_>>();
+    let blocks = ChainBuilder::with_seed(authorities, 0)
+        .push_block()
+        .push_block()
+        .push_block()
+        .push_block()
+        .push_block()
+        .push_block_with_commitment()
+        .build();
+
+    let mut actor = EthereumActor::from_genesis(&blocks[0], 10, None);
+    actor
+        .ingest_new_header(
+            blocks[6].ethereum_view(),
+            generate_signer_proofs(&authority_ids, &[0, 1, 2, 3, 4]),
+        )
+        .unwrap();
+    let claim_proof = blocks[3].build_claim_proof(&blocks[4], &blocks[6]);
+    assert_eq!(actor.verify_claim_proof(None, &claim_proof), Ok(()));
+
+    (actor, blocks, claim_proof)
+}
+
+#[test]
+fn tampered_para_heads_root_fails_mmr_proof() {
+    let (actor, _, mut claim_proof) = setup();
+    claim_proof.para_header_merkle_root = H256::repeat_byte(1);
+    assert_eq!(
+        actor.verify_claim_proof(None, &claim_proof),
+        Err(VerificationError::MmrProofFailed)
+    );
+}
+
+#[test]
+fn tampered_inclusion_proof_fails_para_inclusion() {
+    let (actor, _, mut claim_proof) = setup();
+    let node = &mut claim_proof.para_header_inclusion_proof[0];
+    let last = node.len() - 1;
+    node[last] ^= 1;
+    assert_eq!(
+        actor.verify_claim_proof(None, &claim_proof),
+        Err(VerificationError::ParaInclusionFailed)
+    );
+}
+
+#[test]
+fn tampered_value_fails_storage_proof() {
+    let (actor, _, mut claim_proof) = setup();
+    claim_proof.claimed_kv.1[0] ^= 1;
+    assert_eq!(
+        actor.verify_claim_proof(None, &claim_proof),
+        Err(VerificationError::StorageProofFailed)
+    );
+}
+
+#[test]
+fn storage_proof_of_other_block_fails() {
+    let (actor, blocks, mut claim_proof) = setup();
+    claim_proof.claimed_kv = blocks[2].chosen_kv_pair.clone();
+    claim_proof.kv_proof = blocks[2].chosen_kv_proof.clone();
+    assert_eq!(
+        actor.verify_claim_proof(None, &claim_proof),
+        Err(VerificationError::StorageProofFailed)
+    );
+}
@@ -0,0 +1,25 @@
+// Demo run is checked through its report instead of its output
+
+use beefy_lc_demo::{run_demo, BlockNumber, ValidatorSetId, VerificationError};
+
+#[test]
+fn demo_hands_over_once_and_verifies_claim() {
+    let report = run_demo().unwrap();
+
+    assert_eq!(report.blocks.len(), 12);
+    assert_eq!(report.handovers, vec![BlockNumber(5)]);
+    assert_eq!(report.final_authority_set_id, ValidatorSetId(1));
+    assert!(report.claim_verified);
+}
+
+#[test]
+fn every_demo_block_is_valid() {
+    let mut blocks = run_demo().unwrap().blocks;
+    for block in blocks.iter() {
+        assert_eq!(block.validate(), Ok(()));
+    }
+
+    let block = blocks.last_mut().unwrap();
+    block.chosen_kv_pair.1.push(0);
+    assert_eq!(block.validate(), Err(VerificationError::StorageProofFailed));
+}
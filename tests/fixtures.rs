@@ -0,0 +1,61 @@
+// Fixtures are written where the caller asks, missing directories included
+
+use beefy_lc_demo::{
+    beefy_light_client_demo, export_fixtures, generate_beefy_pairs_from_seed, ChainBuilder,
+};
+use std::fs;
+use std::path::PathBuf;
+
+// Fresh directory in the temp dir, removed once dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("beefy_lc_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let digits: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("\"0x{}\"", digits)
+}
+
+#[test]
+fn fixtures_are_read_back_from_given_path() {
+    let dir = TempDir::new("fixtures");
+    let path = dir.0.join("nested").join("block.json");
+    let blocks = ChainBuilder::with_seed(generate_beefy_pairs_from_seed(5, 0), 0)
+        .push_block()
+        .push_block_with_commitment()
+        .build();
+
+    export_fixtures(&path, &blocks[2]).unwrap();
+
+    let fixtures = fs::read_to_string(&path).unwrap();
+    assert!(fixtures.starts_with('{'));
+    assert!(fixtures.contains("\"block_number\": 3"));
+    assert!(fixtures.contains(&format!(
+        "\"claimed_key\": {}",
+        to_hex(&blocks[2].chosen_kv_pair.0)
+    )));
+}
+
+#[test]
+fn demo_writes_fixtures_to_given_path() {
+    let dir = TempDir::new("demo_fixtures");
+    let path = dir.0.join("last_block.json");
+
+    beefy_light_client_demo(&path).unwrap();
+
+    let fixtures = fs::read_to_string(&path).unwrap();
+    assert!(fixtures.contains("\"block_number\": 12"));
+    assert!(fixtures.contains("\"authority_set_id\": 1"));
+}